@@ -1,6 +1,29 @@
-use crate::error::Result;
-use crate::models::DatabaseSnapshot;
+use crate::error::{DbbaError, Result};
+use crate::models::{
+    CaptureMode, ChangeEvent, ChangeKind, DatabaseSnapshot, Row, TableChecksum, TableFilter,
+    TableSchema, TableSnapshot,
+};
 use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Pool};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Default size of an adapter's connection pool
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+/// Table the triggers of `--mode triggers` record the row changes in
+pub const CHANGELOG_TABLE: &str = "dbba_changelog";
+
+/// Name prefix of the capture triggers, used to find and remove them (including ones
+/// left behind by a run that was killed)
+pub const CAPTURE_TRIGGER_PREFIX: &str = "dbba_capture";
+
+/// Receives the rows of a table one at a time as they are read
+pub type RowSink<'a> = dyn FnMut(Row) -> Result<()> + Send + 'a;
 
 /// Trait for database adapters that can read database snapshots
 #[async_trait]
@@ -11,9 +34,459 @@ pub trait DatabaseAdapter: Send + Sync {
     /// Disconnect from the database
     async fn disconnect(&mut self) -> Result<()>;
 
-    /// Read all tables and their data from the database
-    async fn read_database(&self) -> Result<DatabaseSnapshot>;
+    /// Read the tables selected by `filter` and their data from the database
+    async fn read_database(&self, filter: &TableFilter) -> Result<DatabaseSnapshot> {
+        let mut snapshot = DatabaseSnapshot::new();
+        for table_name in self.list_tables().await? {
+            if !filter.matches(&table_name) {
+                continue;
+            }
+            let table = self.read_table(&table_name).await?;
+            snapshot.insert(table_name, table);
+        }
+        Ok(snapshot)
+    }
+
+    /// Read a single table's key columns and data
+    async fn read_table(&self, table_name: &str) -> Result<TableSnapshot> {
+        let key_columns = self.key_columns(table_name).await?;
+        let schema = self.table_schema(table_name).await?;
+        let mut rows = Vec::new();
+        self.scan_table(table_name, &mut |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .await?;
+        Ok(TableSnapshot {
+            key_columns,
+            rows,
+            schema,
+        })
+    }
+
+    /// Columns identifying the rows of a table (empty if it has no usable key)
+    async fn key_columns(&self, table_name: &str) -> Result<Vec<String>>;
+
+    /// Stream the rows of a table into `sink` without holding the whole table in memory
+    async fn scan_table(&self, table_name: &str, sink: &mut RowSink<'_>) -> Result<()>;
+
+    /// Compute a fingerprint of a table's content without reading its rows, or `None`
+    /// if the database can't compute one (or not as of the consistent snapshot in
+    /// progress)
+    async fn table_checksum(&self, _table_name: &str) -> Result<Option<TableChecksum>> {
+        Ok(None)
+    }
+
+    /// Read the definition of a table, or `None` if the adapter doesn't read table
+    /// definitions
+    async fn table_schema(&self, _table_name: &str) -> Result<Option<TableSchema>> {
+        Ok(None)
+    }
+
+    /// Make all reads until `end_snapshot` see one consistent state of the database,
+    /// using up to `connections` connections that share it.
+    ///
+    /// Returns the number of connections that can read concurrently, which is lower
+    /// than requested when the database can't share the snapshot between connections.
+    async fn begin_snapshot(&self, connections: usize) -> Result<usize> {
+        Ok(connections)
+    }
+
+    /// End the consistent snapshot started by `begin_snapshot`
+    async fn end_snapshot(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Start recording the row changes made to the tables in `table_names`, in the way
+    /// selected by `mode`
+    async fn start_capture(&self, mode: CaptureMode, _table_names: &[String]) -> Result<()> {
+        Err(DbbaError::Config(format!(
+            "--mode {} is not supported by this database",
+            mode
+        )))
+    }
+
+    /// Stop recording and return the row changes made since `start_capture`, in the
+    /// order they were made
+    async fn finish_capture(&self) -> Result<Vec<ChangeEvent>> {
+        Err(DbbaError::Config(
+            "No change capture in progress".to_string(),
+        ))
+    }
+
+    /// Stop recording without reading the changes, removing anything `start_capture`
+    /// installed in the database
+    async fn abort_capture(&self) -> Result<()> {
+        Ok(())
+    }
 
     /// List all table names in the database
     async fn list_tables(&self) -> Result<Vec<String>>;
 }
+
+/// Allows choosing the database adapter at runtime (e.g. `Box<dyn DatabaseAdapter>`)
+#[async_trait]
+impl<T: DatabaseAdapter + ?Sized> DatabaseAdapter for Box<T> {
+    async fn connect(&mut self) -> Result<()> {
+        (**self).connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        (**self).disconnect().await
+    }
+
+    async fn read_database(&self, filter: &TableFilter) -> Result<DatabaseSnapshot> {
+        (**self).read_database(filter).await
+    }
+
+    async fn read_table(&self, table_name: &str) -> Result<TableSnapshot> {
+        (**self).read_table(table_name).await
+    }
+
+    async fn key_columns(&self, table_name: &str) -> Result<Vec<String>> {
+        (**self).key_columns(table_name).await
+    }
+
+    async fn scan_table(&self, table_name: &str, sink: &mut RowSink<'_>) -> Result<()> {
+        (**self).scan_table(table_name, sink).await
+    }
+
+    async fn table_checksum(&self, table_name: &str) -> Result<Option<TableChecksum>> {
+        (**self).table_checksum(table_name).await
+    }
+
+    async fn table_schema(&self, table_name: &str) -> Result<Option<TableSchema>> {
+        (**self).table_schema(table_name).await
+    }
+
+    async fn begin_snapshot(&self, connections: usize) -> Result<usize> {
+        (**self).begin_snapshot(connections).await
+    }
+
+    async fn end_snapshot(&self) -> Result<()> {
+        (**self).end_snapshot().await
+    }
+
+    async fn start_capture(&self, mode: CaptureMode, table_names: &[String]) -> Result<()> {
+        (**self).start_capture(mode, table_names).await
+    }
+
+    async fn finish_capture(&self) -> Result<Vec<ChangeEvent>> {
+        (**self).finish_capture().await
+    }
+
+    async fn abort_capture(&self) -> Result<()> {
+        (**self).abort_capture().await
+    }
+
+    async fn list_tables(&self) -> Result<Vec<String>> {
+        (**self).list_tables().await
+    }
+}
+
+/// Build a change read from the changelog table, whose `operation` is INSERT, UPDATE
+/// or DELETE
+pub(crate) fn changelog_event(
+    sequence: u64,
+    table_name: String,
+    operation: &str,
+    before: Option<Row>,
+    after: Option<Row>,
+) -> Result<ChangeEvent> {
+    let kind = match operation {
+        "INSERT" => ChangeKind::Added,
+        "UPDATE" => ChangeKind::Modified,
+        "DELETE" => ChangeKind::Deleted,
+        _ => {
+            return Err(DbbaError::Unknown(format!(
+                "Unknown operation '{}' in {}",
+                operation, CHANGELOG_TABLE
+            )))
+        }
+    };
+    Ok(ChangeEvent {
+        sequence,
+        table_name,
+        kind,
+        before,
+        after,
+    })
+}
+
+/// A DECIMAL value as an exact JSON number, keeping every digit and the scale; values
+/// that aren't valid JSON numbers are kept as strings
+pub(crate) fn decimal_value(text: &str) -> Value {
+    text.parse()
+        .map(Value::Number)
+        .unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+/// Decode hex digits into bytes, or `None` if they aren't valid hex
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Start a consistent snapshot for reading up to `jobs` tables at a time, returning the
+/// number of tables that can actually be read at a time
+pub(crate) async fn begin_consistent_snapshot<D: DatabaseAdapter + ?Sized>(
+    db_adapter: &D,
+    jobs: usize,
+) -> Result<usize> {
+    let connections = db_adapter.begin_snapshot(jobs).await?.clamp(1, jobs);
+    if connections < jobs {
+        println!(
+            "reading {} table(s) at a time to keep the snapshot consistent",
+            connections
+        );
+    }
+    Ok(connections)
+}
+
+/// Connections taking part in a consistent snapshot, while one is in progress.
+///
+/// Adapters run their queries on these connections instead of the pool, so every read
+/// sees the same state of the database.
+pub(crate) struct SnapshotConnections<DB: Database> {
+    current: Mutex<Option<Arc<ConnectionSet<DB>>>>,
+}
+
+pub(crate) struct ConnectionSet<DB: Database> {
+    idle: Mutex<Vec<PoolConnection<DB>>>,
+    available: Arc<Semaphore>,
+}
+
+impl<DB: Database> Default for SnapshotConnections<DB> {
+    fn default() -> Self {
+        Self {
+            current: Mutex::new(None),
+        }
+    }
+}
+
+impl<DB: Database> SnapshotConnections<DB> {
+    /// Route queries to `connections`, each already inside the snapshot's transaction
+    pub(crate) fn start(&self, connections: Vec<PoolConnection<DB>>) {
+        let set = ConnectionSet {
+            available: Arc::new(Semaphore::new(connections.len())),
+            idle: Mutex::new(connections),
+        };
+        *self.current.lock().unwrap() = Some(Arc::new(set));
+    }
+
+    /// Route queries back to the pool, returning the snapshot's connections so their
+    /// transactions can be ended
+    pub(crate) fn finish(&self) -> Result<Vec<PoolConnection<DB>>> {
+        let Some(set) = self.current.lock().unwrap().take() else {
+            return Ok(Vec::new());
+        };
+        let set = Arc::try_unwrap(set)
+            .map_err(|_| DbbaError::Config("snapshot connections are still in use".to_string()))?;
+        Ok(set.idle.into_inner().unwrap())
+    }
+
+    /// Whether a snapshot is in progress
+    pub(crate) fn is_active(&self) -> bool {
+        self.current.lock().unwrap().is_some()
+    }
+
+    /// A connection of the snapshot if one is in progress, otherwise from the pool
+    pub(crate) async fn acquire(&self, pool: &Pool<DB>) -> Result<AdapterConnection<DB>> {
+        let set = self.current.lock().unwrap().clone();
+        let Some(set) = set else {
+            return Ok(AdapterConnection::Pooled(pool.acquire().await?));
+        };
+
+        let permit = set
+            .available
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| DbbaError::Config(e.to_string()))?;
+        let connection = set.idle.lock().unwrap().pop();
+        Ok(AdapterConnection::Snapshot {
+            connection,
+            set,
+            _permit: permit,
+        })
+    }
+}
+
+/// A connection to run queries on, returned to where it came from when dropped
+pub(crate) enum AdapterConnection<DB: Database> {
+    Pooled(PoolConnection<DB>),
+    Snapshot {
+        connection: Option<PoolConnection<DB>>,
+        set: Arc<ConnectionSet<DB>>,
+        _permit: OwnedSemaphorePermit,
+    },
+}
+
+impl<DB: Database> Deref for AdapterConnection<DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Pooled(connection) => connection,
+            Self::Snapshot { connection, .. } => connection
+                .as_ref()
+                .expect("a permit guarantees an idle connection"),
+        }
+    }
+}
+
+impl<DB: Database> DerefMut for AdapterConnection<DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Pooled(connection) => connection,
+            Self::Snapshot { connection, .. } => connection
+                .as_mut()
+                .expect("a permit guarantees an idle connection"),
+        }
+    }
+}
+
+impl<DB: Database> Drop for AdapterConnection<DB> {
+    fn drop(&mut self) {
+        if let Self::Snapshot {
+            connection, set, ..
+        } = self
+        {
+            if let Some(connection) = connection.take() {
+                set.idle.lock().unwrap().push(connection);
+            }
+        }
+    }
+}
+
+/// A column of a PRIMARY KEY or UNIQUE constraint as reported by the database catalog
+#[derive(Debug, Clone)]
+pub(crate) struct KeyColumn {
+    pub(crate) table_name: String,
+    pub(crate) constraint_name: String,
+    pub(crate) is_primary: bool,
+    pub(crate) column_name: String,
+    pub(crate) is_nullable: bool,
+}
+
+/// Columns of a single constraint, gathered from its `KeyColumn` rows
+#[derive(Default)]
+struct Constraint {
+    is_primary: bool,
+    is_nullable: bool,
+    columns: Vec<String>,
+}
+
+/// Pick the identifying key for each table: the PRIMARY KEY if present, otherwise
+/// the first UNIQUE constraint whose columns are all NOT NULL.
+///
+/// `columns` must be ordered by table, constraint and ordinal position.
+pub(crate) fn select_table_keys(columns: &[KeyColumn]) -> HashMap<String, Vec<String>> {
+    let mut tables: BTreeMap<&str, BTreeMap<&str, Constraint>> = BTreeMap::new();
+    for column in columns {
+        let constraint = tables
+            .entry(&column.table_name)
+            .or_default()
+            .entry(&column.constraint_name)
+            .or_default();
+        constraint.is_primary = column.is_primary;
+        constraint.is_nullable |= column.is_nullable;
+        constraint.columns.push(column.column_name.clone());
+    }
+
+    tables
+        .into_iter()
+        .filter_map(|(table_name, constraints)| {
+            let primary = constraints.values().find(|c| c.is_primary);
+            let unique = constraints
+                .values()
+                .find(|c| !c.is_primary && !c.is_nullable);
+            primary
+                .or(unique)
+                .map(|c| (table_name.to_string(), c.columns.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_column(
+        table_name: &str,
+        constraint_name: &str,
+        column_name: &str,
+        is_nullable: bool,
+    ) -> KeyColumn {
+        KeyColumn {
+            table_name: table_name.to_string(),
+            constraint_name: constraint_name.to_string(),
+            is_primary: constraint_name == "PRIMARY",
+            column_name: column_name.to_string(),
+            is_nullable,
+        }
+    }
+
+    #[test]
+    fn test_decimal_value_is_exact() {
+        let value = decimal_value("12345678901234.5678");
+        assert_eq!(value.to_string(), "12345678901234.5678");
+        assert_ne!(value, decimal_value("12345678901234.5679"));
+        assert_eq!(decimal_value("-0.10").to_string(), "-0.10");
+        assert_eq!(decimal_value("NaN"), Value::String("NaN".to_string()));
+    }
+
+    #[test]
+    fn test_select_table_keys_prefers_primary_key() {
+        let columns = vec![
+            key_column("users", "PRIMARY", "uuid", false),
+            key_column("users", "index_users_on_email", "email", false),
+        ];
+
+        let keys = select_table_keys(&columns);
+
+        assert_eq!(keys.get("users"), Some(&vec!["uuid".to_string()]));
+    }
+
+    #[test]
+    fn test_select_table_keys_composite_primary_key() {
+        let columns = vec![
+            key_column("user_roles", "PRIMARY", "user_id", false),
+            key_column("user_roles", "PRIMARY", "role_id", false),
+        ];
+
+        let keys = select_table_keys(&columns);
+
+        assert_eq!(
+            keys.get("user_roles"),
+            Some(&vec!["user_id".to_string(), "role_id".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_select_table_keys_falls_back_to_unique_not_null() {
+        let columns = vec![
+            key_column("order_items", "index_a_nullable", "external_ref", true),
+            key_column("order_items", "index_b", "order_id", false),
+            key_column("order_items", "index_b", "line_no", false),
+        ];
+
+        let keys = select_table_keys(&columns);
+
+        assert_eq!(
+            keys.get("order_items"),
+            Some(&vec!["order_id".to_string(), "line_no".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_select_table_keys_ignores_nullable_unique() {
+        let columns = vec![key_column("logs", "index_logs_on_ref", "ref", true)];
+
+        let keys = select_table_keys(&columns);
+
+        assert!(!keys.contains_key("logs"));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::adapters::OutputAdapter;
 use crate::error::Result;
+use crate::models::{ChangeKind, Row, RowChange, SchemaChange, TableStatus, Timezone};
 use similar::{ChangeTag, TextDiff};
 use std::io::Write;
 
@@ -81,6 +82,27 @@ impl<W: Write> HtmlOutputAdapter<W> {
           margin-top: 1rem;
         }
 
+        .diff-note {
+          font-size: 0.95rem;
+          color: #6c757d;
+          margin-bottom: 0.75rem;
+        }
+
+        .column-changes {
+          list-style: none;
+          margin-bottom: 0.75rem;
+          font-family: 'Monaco', 'Menlo', 'Ubuntu Mono', monospace;
+          font-size: 0.9rem;
+        }
+
+        .column-changes li {
+          padding: 0.1rem 0;
+        }
+
+        .column-changes li.ignored {
+          color: #adb5bd;
+        }
+
         .diff-part {
           display: flex;
           gap: 2rem;
@@ -300,6 +322,63 @@ impl<W: Write> HtmlOutputAdapter<W> {
         "#
     }
 
+    /// Pretty-printed JSON of a row, or an empty string when the row is absent
+    fn pretty_row(row: Option<&Row>) -> String {
+        row.and_then(|row| serde_json::to_string_pretty(row).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write a diff section with left (before) and right (after) content
+    fn write_diff_section(&mut self, left: &str, right: &str) -> Result<()> {
+        write!(
+            self.writer,
+            r#"<div class="diff-part">
+  <div class="diff-side diff-left">
+    <h3 class="diff-header">Before</h3>
+    <div class="diff-content">{}</div>
+  </div>
+  <div class="diff-side diff-right">
+    <h3 class="diff-header">After</h3>
+    <div class="diff-content">{}</div>
+  </div>
+</div>
+"#,
+            left, right
+        )?;
+        Ok(())
+    }
+
+    /// Generate diff HTML from left and right content
+    fn generate_diff(left: &str, right: &str) -> (String, String) {
+        let diff = TextDiff::from_lines(left, right);
+
+        let mut left_html = String::from("<div class=\"diff\"><ul>");
+        let mut right_html = String::from("<div class=\"diff\"><ul>");
+
+        for change in diff.iter_all_changes() {
+            let line = Self::html_escape(change.value());
+            match change.tag() {
+                ChangeTag::Delete => {
+                    left_html.push_str(&format!("<li class=\"del\"><del>{}</del></li>", line));
+                    right_html.push_str("<li class=\"del\"><del></del></li>");
+                }
+                ChangeTag::Insert => {
+                    left_html.push_str("<li class=\"ins\"><ins></ins></li>");
+                    right_html.push_str(&format!("<li class=\"ins\"><ins>{}</ins></li>", line));
+                }
+                ChangeTag::Equal => {
+                    left_html.push_str(&format!("<li><span>{}</span></li>", line));
+                    right_html.push_str(&format!("<li><span>{}</span></li>", line));
+                }
+            }
+        }
+
+        left_html.push_str("</ul></div>");
+        right_html.push_str("</ul></div>");
+
+        (left_html, right_html)
+    }
+
     /// HTML escape a string
     fn html_escape(s: &str) -> String {
         s.replace('&', "&amp;")
@@ -368,25 +447,85 @@ impl<W: Write> OutputAdapter for HtmlOutputAdapter<W> {
         Ok(())
     }
 
-    fn write_diff_section(&mut self, left: &str, right: &str) -> Result<()> {
-        write!(
+    fn write_timezone(&mut self, timezone: Timezone) -> Result<()> {
+        writeln!(
             self.writer,
-            r#"<div class="diff-part">
-  <div class="diff-side diff-left">
-    <h3 class="diff-header">Before</h3>
-    <div class="diff-content">{}</div>
-  </div>
-  <div class="diff-side diff-right">
-    <h3 class="diff-header">After</h3>
-    <div class="diff-content">{}</div>
-  </div>
-</div>
-"#,
-            left, right
+            r#"<p class="diff-note timezone">TIMESTAMP values in {}</p>"#,
+            timezone
         )?;
         Ok(())
     }
 
+    fn write_table_status(&mut self, status: TableStatus) -> Result<()> {
+        writeln!(
+            self.writer,
+            r#"<p class="diff-note table-status table-{}">Table {}</p>"#,
+            status, status
+        )?;
+        Ok(())
+    }
+
+    fn write_note(&mut self, note: &str) -> Result<()> {
+        writeln!(
+            self.writer,
+            r#"<p class="diff-note">{}</p>"#,
+            Self::html_escape(note)
+        )?;
+        Ok(())
+    }
+
+    fn write_row_change(&mut self, change: &RowChange) -> Result<()> {
+        if let Some(sequence) = change.sequence {
+            let statement = match change.kind {
+                ChangeKind::Added => "INSERT",
+                ChangeKind::Modified => "UPDATE",
+                ChangeKind::Deleted => "DELETE",
+            };
+            self.write_note(&format!("#{} {} {}", sequence, statement, change.key))?;
+        }
+
+        if let Some(count) = &change.copy_count {
+            self.write_note(&count.summary())?;
+        }
+
+        if !change.changed_columns.is_empty() {
+            writeln!(self.writer, r#"<ul class="column-changes">"#)?;
+            for column in &change.changed_columns {
+                for summary in column.summaries() {
+                    if column.ignored {
+                        writeln!(
+                            self.writer,
+                            r#"  <li class="ignored">{} (ignored)</li>"#,
+                            Self::html_escape(&summary)
+                        )?;
+                    } else {
+                        writeln!(self.writer, "  <li>{}</li>", Self::html_escape(&summary))?;
+                    }
+                }
+            }
+            writeln!(self.writer, "</ul>")?;
+        }
+
+        let left = Self::pretty_row(change.before.as_ref());
+        let right = Self::pretty_row(change.after.as_ref());
+        let (left_html, right_html) = Self::generate_diff(&left, &right);
+        self.write_diff_section(&left_html, &right_html)
+    }
+
+    fn write_schema_changes(&mut self, changes: &[SchemaChange]) -> Result<()> {
+        self.write_title("Schema Changes")?;
+        writeln!(self.writer, r#"<ul class="column-changes">"#)?;
+        for change in changes {
+            writeln!(
+                self.writer,
+                "  <li>{}</li>",
+                Self::html_escape(&change.summary())
+            )?;
+        }
+        writeln!(self.writer, "</ul>")?;
+        self.close_section()
+    }
+
     fn write_no_diff_message(&mut self) -> Result<()> {
         write!(
             self.writer,
@@ -409,34 +548,4 @@ impl<W: Write> OutputAdapter for HtmlOutputAdapter<W> {
         )?;
         Ok(())
     }
-
-    fn generate_diff(&self, left: &str, right: &str) -> (String, String) {
-        let diff = TextDiff::from_lines(left, right);
-
-        let mut left_html = String::from("<div class=\"diff\"><ul>");
-        let mut right_html = String::from("<div class=\"diff\"><ul>");
-
-        for change in diff.iter_all_changes() {
-            let line = Self::html_escape(change.value());
-            match change.tag() {
-                ChangeTag::Delete => {
-                    left_html.push_str(&format!("<li class=\"del\"><del>{}</del></li>", line));
-                    right_html.push_str("<li class=\"del\"><del></del></li>");
-                }
-                ChangeTag::Insert => {
-                    left_html.push_str("<li class=\"ins\"><ins></ins></li>");
-                    right_html.push_str(&format!("<li class=\"ins\"><ins>{}</ins></li>", line));
-                }
-                ChangeTag::Equal => {
-                    left_html.push_str(&format!("<li><span>{}</span></li>", line));
-                    right_html.push_str(&format!("<li><span>{}</span></li>", line));
-                }
-            }
-        }
-
-        left_html.push_str("</ul></div>");
-        right_html.push_str("</ul></div>");
-
-        (left_html, right_html)
-    }
 }
@@ -0,0 +1,268 @@
+use crate::adapters::OutputAdapter;
+use crate::error::{DbbaError, Result};
+use crate::models::{RowChange, SchemaChange, TableStatus, Timezone};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// JSON output adapter that generates machine-readable diff reports
+///
+/// The whole report is written as a single JSON document when the output ends: tables
+/// by name, and each table's changed rows by row key, in the order they were written.
+/// Changes recorded by the database are listed in the order they were made instead,
+/// and changes to table definitions under `schema_changes`.
+pub struct JsonOutputAdapter<W: Write> {
+    writer: W,
+    report: JsonReport,
+    current_table: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct JsonReport {
+    generated_at: String,
+    /// Time zone TIMESTAMP values are shown in, as noted by `write_timezone`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timezone: Option<Timezone>,
+    has_changes: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    schema_changes: Vec<SchemaChange>,
+    tables: BTreeMap<String, JsonTable>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct JsonTable {
+    /// Whether the table was created or dropped (absent for existing tables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<TableStatus>,
+    notes: Vec<String>,
+    #[serde(serialize_with = "rows_by_key")]
+    rows: Vec<RowChange>,
+    /// Recorded changes in the order they were made (change capture modes only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    changes: Vec<RowChange>,
+}
+
+/// Serialize changed rows as an object keyed by row key, keeping their order
+fn rows_by_key<S: Serializer>(
+    rows: &[RowChange],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_map(rows.iter().map(|row| (&row.key, row)))
+}
+
+impl<W: Write> JsonOutputAdapter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            report: JsonReport::default(),
+            current_table: None,
+        }
+    }
+
+    /// Get the table of the current section
+    fn current_table(&mut self) -> Result<&mut JsonTable> {
+        self.current_table
+            .as_ref()
+            .and_then(|name| self.report.tables.get_mut(name))
+            .ok_or_else(|| DbbaError::Unknown("No table section is open".to_string()))
+    }
+}
+
+impl<W: Write> OutputAdapter for JsonOutputAdapter<W> {
+    fn start_output(&mut self) -> Result<()> {
+        self.report = JsonReport {
+            generated_at: chrono::Local::now().to_rfc3339(),
+            ..JsonReport::default()
+        };
+        Ok(())
+    }
+
+    fn end_output(&mut self) -> Result<()> {
+        self.report.has_changes =
+            !self.report.tables.is_empty() || !self.report.schema_changes.is_empty();
+        serde_json::to_writer_pretty(&mut self.writer, &self.report)?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_timezone(&mut self, timezone: Timezone) -> Result<()> {
+        self.report.timezone = Some(timezone);
+        Ok(())
+    }
+
+    fn write_title(&mut self, title: &str) -> Result<()> {
+        self.report.tables.entry(title.to_string()).or_default();
+        self.current_table = Some(title.to_string());
+        Ok(())
+    }
+
+    fn write_table_status(&mut self, status: TableStatus) -> Result<()> {
+        self.current_table()?.status = Some(status);
+        Ok(())
+    }
+
+    fn write_note(&mut self, note: &str) -> Result<()> {
+        self.current_table()?.notes.push(note.to_string());
+        Ok(())
+    }
+
+    fn write_row_change(&mut self, change: &RowChange) -> Result<()> {
+        let table = self.current_table()?;
+        if change.sequence.is_some() {
+            table.changes.push(change.clone());
+        } else {
+            table.rows.push(change.clone());
+        }
+        Ok(())
+    }
+
+    fn write_schema_changes(&mut self, changes: &[SchemaChange]) -> Result<()> {
+        self.report.schema_changes.extend_from_slice(changes);
+        Ok(())
+    }
+
+    fn write_no_diff_message(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close_section(&mut self) -> Result<()> {
+        self.current_table = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChangeKind, ColumnChange, CopyCount};
+    use serde_json::{json, Value};
+
+    fn row(pairs: &[(&str, Value)]) -> crate::models::Row {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_writes_rows_by_table_and_key() {
+        let mut output = Vec::new();
+        let mut adapter = JsonOutputAdapter::new(&mut output);
+
+        adapter.start_output().unwrap();
+        adapter.write_title("orders").unwrap();
+        adapter
+            .write_row_change(&RowChange {
+                key: "1".to_string(),
+                kind: ChangeKind::Modified,
+                before: Some(row(&[("id", json!(1)), ("status", json!("pending"))])),
+                after: Some(row(&[("id", json!(1)), ("status", json!("shipped"))])),
+                changed_columns: vec![ColumnChange::new(
+                    "status",
+                    json!("pending"),
+                    json!("shipped"),
+                )],
+                copy_count: None,
+                sequence: None,
+            })
+            .unwrap();
+        adapter.close_section().unwrap();
+        adapter.write_title("logs").unwrap();
+        adapter.write_note("Rows compared by content").unwrap();
+        adapter
+            .write_row_change(&RowChange {
+                key: "abc".to_string(),
+                kind: ChangeKind::Added,
+                before: None,
+                after: Some(row(&[("message", json!("hello"))])),
+                changed_columns: Vec::new(),
+                copy_count: Some(CopyCount {
+                    before: 0,
+                    after: 1,
+                }),
+                sequence: None,
+            })
+            .unwrap();
+        adapter.close_section().unwrap();
+        adapter.end_output().unwrap();
+
+        let report: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["has_changes"], json!(true));
+        let order = &report["tables"]["orders"]["rows"]["1"];
+        assert_eq!(order["kind"], json!("modified"));
+        assert_eq!(order["before"]["status"], json!("pending"));
+        assert_eq!(order["after"]["status"], json!("shipped"));
+        assert_eq!(
+            order["changed_columns"],
+            json!([{"column": "status", "before": "pending", "after": "shipped"}])
+        );
+        assert!(order.get("copy_count").is_none());
+        let log = &report["tables"]["logs"];
+        assert_eq!(log["notes"], json!(["Rows compared by content"]));
+        assert_eq!(log["rows"]["abc"]["before"], Value::Null);
+        assert_eq!(
+            log["rows"]["abc"]["copy_count"],
+            json!({"before": 0, "after": 1})
+        );
+    }
+
+    #[test]
+    fn test_writes_recorded_changes_in_order() {
+        let mut output = Vec::new();
+        let mut adapter = JsonOutputAdapter::new(&mut output);
+
+        adapter.start_output().unwrap();
+        adapter.write_title("users").unwrap();
+        for (sequence, name) in [(1, "John"), (2, "Jane")] {
+            adapter
+                .write_row_change(&RowChange {
+                    key: "1".to_string(),
+                    kind: ChangeKind::Added,
+                    before: None,
+                    after: Some(row(&[("id", json!(1)), ("name", json!(name))])),
+                    changed_columns: Vec::new(),
+                    copy_count: None,
+                    sequence: Some(sequence),
+                })
+                .unwrap();
+        }
+        adapter.close_section().unwrap();
+        adapter.end_output().unwrap();
+
+        let report: Value = serde_json::from_slice(&output).unwrap();
+        let users = &report["tables"]["users"];
+        assert_eq!(users["rows"], json!({}));
+        assert_eq!(users["changes"][0]["sequence"], json!(1));
+        assert_eq!(users["changes"][1]["after"]["name"], json!("Jane"));
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let mut output = Vec::new();
+        let mut adapter = JsonOutputAdapter::new(&mut output);
+
+        adapter.start_output().unwrap();
+        adapter.write_no_diff_message().unwrap();
+        adapter.end_output().unwrap();
+
+        let report: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["has_changes"], json!(false));
+        assert_eq!(report["tables"], json!({}));
+        assert!(report.get("timezone").is_none());
+    }
+
+    #[test]
+    fn test_writes_timezone() {
+        let mut output = Vec::new();
+        let mut adapter = JsonOutputAdapter::new(&mut output);
+
+        adapter.start_output().unwrap();
+        adapter.write_timezone("+09:00".parse().unwrap()).unwrap();
+        adapter.write_no_diff_message().unwrap();
+        adapter.end_output().unwrap();
+
+        let report: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["timezone"], json!("+09:00"));
+    }
+}
@@ -1,9 +1,16 @@
 pub mod database;
 pub mod html_output;
+pub mod json_output;
 pub mod mysql;
+mod mysql_binlog;
 pub mod output;
+pub mod postgres;
+pub mod sqlite;
 
 pub use database::DatabaseAdapter;
 pub use html_output::HtmlOutputAdapter;
+pub use json_output::JsonOutputAdapter;
 pub use mysql::MySqlAdapter;
 pub use output::OutputAdapter;
+pub use postgres::PostgresAdapter;
+pub use sqlite::SqliteAdapter;
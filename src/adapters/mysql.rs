@@ -1,28 +1,100 @@
+use crate::adapters::database::{
+    changelog_event, decimal_value, decode_hex, select_table_keys, AdapterConnection, KeyColumn,
+    RowSink, SnapshotConnections, CAPTURE_TRIGGER_PREFIX, CHANGELOG_TABLE, DEFAULT_MAX_CONNECTIONS,
+};
+use crate::adapters::mysql_binlog::{
+    check_plain_connection, float, fractional_seconds, timestamp_value, BinlogClient,
+    BinlogPosition, ColumnInfo, EventDecoder,
+};
 use crate::adapters::DatabaseAdapter;
 use crate::error::{DbbaError, Result};
-use crate::models::{DatabaseSnapshot, DbInfo};
+use crate::models::{
+    BinaryFormat, BinaryFormats, CaptureMode, ChangeEvent, ColumnDefinition, DbInfo, TableChecksum,
+    TableSchema, Timezone,
+};
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
-use md5::{Digest, Md5};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use futures_util::TryStreamExt;
 use serde_json::Value;
-use sqlx::mysql::{MySqlPool, MySqlRow};
-use sqlx::{Column, Row, TypeInfo, ValueRef};
+use sqlx::mysql::types::MySqlTime;
+use sqlx::mysql::{MySql, MySqlConnection, MySqlPool, MySqlPoolOptions, MySqlRow};
+use sqlx::pool::PoolConnection;
+use sqlx::{Column, Executor, Row, TypeInfo, ValueRef};
 use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 
 /// MySQL database adapter
 pub struct MySqlAdapter {
     db_info: DbInfo,
+    max_connections: u32,
+    binary_formats: BinaryFormats,
+    timezone: Timezone,
     pool: Option<MySqlPool>,
+    snapshot: SnapshotConnections<MySql>,
+    /// Change capture started by `start_capture`
+    capture: Mutex<Option<Capture>>,
+}
+
+/// A change capture in progress
+enum Capture {
+    /// Reading the binary log from `start`, decoding rows of `schema` with the column
+    /// layout of its tables when the capture started
+    Binlog {
+        start: BinlogPosition,
+        schema: String,
+        columns: HashMap<String, Vec<ColumnInfo>>,
+    },
+    /// Recording with triggers into the changelog table
+    Triggers,
+}
+
+/// A column whose values the capture triggers record in a form decoded afterwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChangelogColumn {
+    /// Hex digits of a binary or GEOMETRY value, shown in this format
+    Binary(BinaryFormat),
+    /// Seconds since the epoch of a TIMESTAMP value with this many digits of
+    /// fractional seconds
+    Timestamp(usize),
 }
 
 impl MySqlAdapter {
     pub fn new(db_info: DbInfo) -> Self {
         Self {
             db_info,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            binary_formats: BinaryFormats::default(),
+            timezone: Timezone::default(),
             pool: None,
+            snapshot: SnapshotConnections::default(),
+            capture: Mutex::new(None),
         }
     }
 
+    /// Set the maximum number of connections used to read tables concurrently
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Set how the values of binary and GEOMETRY columns are shown
+    pub fn with_binary_formats(mut self, binary_formats: BinaryFormats) -> Self {
+        self.binary_formats = binary_formats;
+        self
+    }
+
+    /// Set the time zone TIMESTAMP values are shown in (default: UTC)
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Get a connection to run queries on, inside the consistent snapshot if one is in
+    /// progress
+    async fn connection(&self) -> Result<AdapterConnection<MySql>> {
+        self.snapshot.acquire(self.pool()?).await
+    }
+
     /// Get the connection pool
     fn pool(&self) -> Result<&MySqlPool> {
         self.pool
@@ -30,69 +102,409 @@ impl MySqlAdapter {
             .ok_or_else(|| DbbaError::Config("Database not connected".to_string()))
     }
 
-    /// Convert a MySQL row to a BTreeMap (sorted keys)
-    fn row_to_hashmap(row: &MySqlRow) -> Result<BTreeMap<String, Value>> {
-        let mut map = BTreeMap::new();
+    /// Read a string column that MySQL may return as either VARCHAR or VARBINARY
+    fn get_string(row: &MySqlRow, index: usize) -> Option<String> {
+        if let Ok(s) = row.try_get::<String, _>(index) {
+            Some(s)
+        } else if let Ok(b) = row.try_get::<Vec<u8>, _>(index) {
+            Some(String::from_utf8_lossy(&b).to_string())
+        } else {
+            None
+        }
+    }
 
-        for (i, column) in row.columns().iter().enumerate() {
-            let column_name = column.name();
-            let type_info = column.type_info();
-            let type_name = type_info.name();
+    /// Start a consistent snapshot transaction on `first` and on `others` more connections
+    async fn start_transactions(
+        pool: &MySqlPool,
+        first: &mut MySqlConnection,
+        others: usize,
+    ) -> Result<Vec<PoolConnection<MySql>>> {
+        const START: &str = "START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY";
 
-            // Check if the value is NULL first
-            let raw_value = row.try_get_raw(i)?;
-            if raw_value.is_null() {
-                map.insert(column_name.to_string(), Value::Null);
+        first.execute(START).await?;
+        let mut connections = Vec::new();
+        for _ in 0..others {
+            let mut connection = pool.acquire().await?;
+            (&mut *connection).execute(START).await?;
+            connections.push(connection);
+        }
+        Ok(connections)
+    }
+
+    /// Current position of the binary log
+    async fn binlog_position(&self) -> Result<BinlogPosition> {
+        let mut connection = self.connection().await?;
+
+        // SHOW MASTER STATUS was renamed in MySQL 8.2
+        let row = match sqlx::query("SHOW BINARY LOG STATUS")
+            .fetch_optional(&mut *connection)
+            .await
+        {
+            Ok(row) => row,
+            Err(_) => {
+                sqlx::query("SHOW MASTER STATUS")
+                    .fetch_optional(&mut *connection)
+                    .await?
+            }
+        };
+        let row = row.ok_or_else(|| {
+            DbbaError::Config("Binary logging is disabled on this server".to_string())
+        })?;
+
+        Ok(BinlogPosition {
+            file: Self::get_string(&row, 0).unwrap_or_default(),
+            position: row.try_get(1)?,
+        })
+    }
+
+    /// Check that the binary log records full row images
+    async fn check_binlog_format(&self) -> Result<()> {
+        let mut connection = self.connection().await?;
+        let row = sqlx::query("SELECT @@global.binlog_format, @@global.binlog_row_image")
+            .fetch_one(&mut *connection)
+            .await?;
+        let format = Self::get_string(&row, 0).unwrap_or_default();
+        let row_image = Self::get_string(&row, 1).unwrap_or_default();
+        if !format.eq_ignore_ascii_case("ROW") || !row_image.eq_ignore_ascii_case("FULL") {
+            return Err(DbbaError::Config(format!(
+                "--mode binlog needs binlog_format=ROW and binlog_row_image=FULL (server has {} and {})",
+                format, row_image
+            )));
+        }
+        Ok(())
+    }
+
+    /// Columns of a table by name, used to decode its rows
+    async fn table_columns(
+        &self,
+        connection: &mut MySqlConnection,
+        table_name: &str,
+    ) -> Result<HashMap<String, ColumnInfo>> {
+        let query = r#"
+            SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE
+            FROM INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut columns = HashMap::new();
+        for row in rows {
+            let (Some(column_name), Some(data_type), Some(column_type)) = (
+                Self::get_string(&row, 0),
+                Self::get_string(&row, 1),
+                Self::get_string(&row, 2),
+            ) else {
                 continue;
+            };
+            let info = ColumnInfo::new(column_name.clone(), &data_type, &column_type)
+                .with_binary_format(self.binary_formats.format(table_name, &column_name))
+                .with_timezone(self.timezone);
+            columns.insert(column_name, info);
+        }
+        Ok(columns)
+    }
+
+    /// Current database and the columns of its tables, used to decode binlog row images
+    async fn binlog_columns(&self) -> Result<(String, HashMap<String, Vec<ColumnInfo>>)> {
+        let mut connection = self.connection().await?;
+        let schema: Option<String> = sqlx::query_scalar("SELECT DATABASE()")
+            .fetch_one(&mut *connection)
+            .await?;
+        let query = r#"
+            SELECT TABLE_NAME, COLUMN_NAME, DATA_TYPE, COLUMN_TYPE
+            FROM INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE()
+            ORDER BY TABLE_NAME, ORDINAL_POSITION
+        "#;
+        let rows = sqlx::query(query).fetch_all(&mut *connection).await?;
+
+        let mut columns: HashMap<String, Vec<ColumnInfo>> = HashMap::new();
+        for row in rows {
+            let (Some(table_name), Some(column_name), Some(data_type), Some(column_type)) = (
+                Self::get_string(&row, 0),
+                Self::get_string(&row, 1),
+                Self::get_string(&row, 2),
+                Self::get_string(&row, 3),
+            ) else {
+                continue;
+            };
+            let binary_format = self.binary_formats.format(&table_name, &column_name);
+            columns.entry(table_name).or_default().push(
+                ColumnInfo::new(column_name, &data_type, &column_type)
+                    .with_binary_format(binary_format)
+                    .with_timezone(self.timezone),
+            );
+        }
+
+        Ok((schema.unwrap_or_default(), columns))
+    }
+
+    /// Remove the capture triggers and the changelog table, including ones left behind
+    /// by an earlier run
+    async fn drop_capture(&self) -> Result<()> {
+        let mut connection = self.pool()?.acquire().await?;
+        let query = r#"
+            SELECT TRIGGER_NAME FROM INFORMATION_SCHEMA.TRIGGERS
+            WHERE TRIGGER_SCHEMA = DATABASE() AND TRIGGER_NAME LIKE ?
+        "#;
+        let rows = sqlx::query(query)
+            .bind(format!("{}\\_%", CAPTURE_TRIGGER_PREFIX))
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            if let Some(trigger) = Self::get_string(&row, 0) {
+                let statement = format!("DROP TRIGGER IF EXISTS `{}`", trigger);
+                (&mut *connection).execute(statement.as_str()).await?;
             }
+        }
 
-            // Handle different MySQL types based on type name
-            let value: Value = match type_name {
-                "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
-                    row.try_get::<i64, _>(i)
-                        .map(|v| Value::Number(v.into()))
-                        .unwrap_or(Value::Null)
-                }
-                "FLOAT" | "DOUBLE" | "DECIMAL" => {
-                    row.try_get::<f64, _>(i)
-                        .ok()
-                        .and_then(|v| serde_json::Number::from_f64(v))
-                        .map(Value::Number)
-                        .unwrap_or(Value::Null)
-                }
-                "DATETIME" | "TIMESTAMP" => {
-                    row.try_get::<NaiveDateTime, _>(i)
-                        .map(|v| Value::String(v.format("%Y-%m-%d %H:%M:%S").to_string()))
-                        .unwrap_or(Value::Null)
-                }
-                "BLOB" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" | "BINARY" | "VARBINARY" => {
-                    row.try_get::<Vec<u8>, _>(i)
-                        .map(|v| {
-                            let mut hasher = Md5::new();
-                            hasher.update(&v);
-                            let hash = hasher.finalize();
-                            Value::String(format!("MD5 Digest value: {:x}", hash))
-                        })
-                        .unwrap_or(Value::Null)
-                }
-                "BIT" => {
-                    row.try_get::<bool, _>(i)
-                        .map(Value::Bool)
-                        .unwrap_or(Value::Null)
-                }
-                // Try Vec<u8> first for unknown types (might be binary), then String
-                _ => {
-                    if let Ok(v) = row.try_get::<Vec<u8>, _>(i) {
-                        let mut hasher = Md5::new();
-                        hasher.update(&v);
-                        let hash = hasher.finalize();
-                        Value::String(format!("MD5 Digest value: {:x}", hash))
-                    } else if let Ok(v) = row.try_get::<String, _>(i) {
-                        Value::String(v)
-                    } else {
-                        Value::Null
+        let statement = format!("DROP TABLE IF EXISTS `{}`", CHANGELOG_TABLE);
+        (&mut *connection).execute(statement.as_str()).await?;
+        Ok(())
+    }
+
+    /// `JSON_OBJECT` recording the columns of the `OLD` or `NEW` row of a table, with
+    /// values formatted as when reading the table.
+    ///
+    /// Binary values can't be held in JSON, so they are recorded as a one-element array
+    /// of their hex digits and rendered when the changes are read. TIMESTAMP values are
+    /// recorded as seconds since the epoch, which needs no time zone tables whatever the
+    /// session time zone of the use case, and shown in the chosen time zone when read.
+    async fn row_json(
+        &self,
+        connection: &mut MySqlConnection,
+        table_name: &str,
+        row: &str,
+    ) -> Result<String> {
+        let query = r#"
+            SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE
+            FROM INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+            ORDER BY ORDINAL_POSITION
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut arguments = Vec::new();
+        for row_data in rows {
+            let (Some(column), Some(data_type), Some(column_type)) = (
+                Self::get_string(&row_data, 0),
+                Self::get_string(&row_data, 1),
+                Self::get_string(&row_data, 2),
+            ) else {
+                continue;
+            };
+            let value = format!("{}.`{}`", row, column);
+            let value = match data_type.to_ascii_lowercase().as_str() {
+                "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob"
+                | "geometry" => format!(
+                    "IF({v} IS NULL, NULL, JSON_ARRAY(HEX({v})))",
+                    v = value
+                ),
+                // As text, with the fractional seconds of the column's precision
+                "datetime" | "time" => format!("CAST({} AS CHAR)", value),
+                "timestamp" => format!("UNIX_TIMESTAMP({})", value),
+                "bit" if column_type.eq_ignore_ascii_case("bit(1)") => format!(
+                    "CASE WHEN {v} IS NULL THEN NULL WHEN {v} <> 0 THEN CAST('true' AS JSON) ELSE CAST('false' AS JSON) END",
+                    v = value
+                ),
+                "bit" => format!("{} + 0", value),
+                _ => value,
+            };
+            arguments.push(format!("{}, {}", Self::quote_literal(&column), value));
+        }
+
+        Ok(format!("JSON_OBJECT({})", arguments.join(", ")))
+    }
+
+    /// Quote a string literal for use in SQL
+    fn quote_literal(value: &str) -> String {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+    }
+
+    /// The binary, GEOMETRY and TIMESTAMP columns of a table, whose values are decoded
+    /// when the changes are read
+    async fn changelog_columns(
+        &self,
+        connection: &mut MySqlConnection,
+        table_name: &str,
+    ) -> Result<HashMap<String, ChangelogColumn>> {
+        let query = r#"
+            SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE
+            FROM INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND DATA_TYPE IN
+                ('binary', 'varbinary', 'tinyblob', 'blob', 'mediumblob', 'longblob', 'geometry',
+                 'timestamp')
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut changelog_columns = HashMap::new();
+        for row in rows {
+            let (Some(column), Some(data_type), Some(column_type)) = (
+                Self::get_string(&row, 0),
+                Self::get_string(&row, 1),
+                Self::get_string(&row, 2),
+            ) else {
+                continue;
+            };
+            let changelog_column = if data_type.eq_ignore_ascii_case("timestamp") {
+                let info = ColumnInfo::new(column.clone(), &data_type, &column_type);
+                ChangelogColumn::Timestamp(info.precision)
+            } else {
+                ChangelogColumn::Binary(self.binary_formats.format(table_name, &column))
+            };
+            changelog_columns.insert(column, changelog_column);
+        }
+        Ok(changelog_columns)
+    }
+
+    /// Parse a row recorded by the capture triggers, rendering the hex digits of binary
+    /// values in their binary format and TIMESTAMP values in `timezone`
+    fn changelog_row(
+        json: Option<String>,
+        columns: &HashMap<String, ChangelogColumn>,
+        timezone: Timezone,
+    ) -> Result<Option<BTreeMap<String, Value>>> {
+        let Some(json) = json else {
+            return Ok(None);
+        };
+        let mut row: BTreeMap<String, Value> = serde_json::from_str(&json)?;
+        for (column, value) in row.iter_mut() {
+            match (columns.get(column), &*value) {
+                (Some(ChangelogColumn::Binary(format)), Value::Array(items)) => {
+                    if let [Value::String(hex)] = items.as_slice() {
+                        if let Some(bytes) = decode_hex(hex) {
+                            *value = format.render(&bytes);
+                        }
                     }
                 }
+                (Some(ChangelogColumn::Timestamp(precision)), Value::Number(seconds)) => {
+                    // e.g. `1704164645.123`, exactly as recorded
+                    let seconds = seconds.to_string();
+                    let (whole, fraction) = seconds.split_once('.').unwrap_or((&seconds, ""));
+                    let Ok(whole) = whole.parse() else {
+                        continue;
+                    };
+                    let microseconds = format!("{:0<6}", fraction)[..6].parse().unwrap_or(0);
+                    *value = timestamp_value(whole, microseconds, *precision, timezone);
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(row))
+    }
+
+    /// Install the triggers recording the changes of `table_names`
+    async fn start_triggers(&self, table_names: &[String]) -> Result<()> {
+        self.drop_capture().await?;
+
+        let mut connection = self.pool()?.acquire().await?;
+        let statement = format!(
+            "CREATE TABLE `{}` (id BIGINT AUTO_INCREMENT PRIMARY KEY, \
+             table_name VARCHAR(64) NOT NULL, operation VARCHAR(6) NOT NULL, \
+             old_row JSON, new_row JSON) ENGINE = InnoDB",
+            CHANGELOG_TABLE
+        );
+        (&mut *connection).execute(statement.as_str()).await?;
+
+        let tables = table_names
+            .iter()
+            .filter(|table_name| table_name.as_str() != CHANGELOG_TABLE);
+        for (i, table_name) in tables.enumerate() {
+            let old_row = self.row_json(&mut connection, table_name, "OLD").await?;
+            let new_row = self.row_json(&mut connection, table_name, "NEW").await?;
+            for (operation, old_row, new_row) in [
+                ("INSERT", "NULL", new_row.as_str()),
+                ("UPDATE", old_row.as_str(), new_row.as_str()),
+                ("DELETE", old_row.as_str(), "NULL"),
+            ] {
+                let statement = format!(
+                    "CREATE TRIGGER `{}_{}_{}` AFTER {} ON `{}` FOR EACH ROW \
+                     INSERT INTO `{}` (table_name, operation, old_row, new_row) \
+                     VALUES ({}, '{}', {}, {})",
+                    CAPTURE_TRIGGER_PREFIX,
+                    i,
+                    operation.to_lowercase(),
+                    operation,
+                    table_name,
+                    CHANGELOG_TABLE,
+                    Self::quote_literal(table_name),
+                    operation,
+                    old_row,
+                    new_row
+                );
+                (&mut *connection).execute(statement.as_str()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the changes recorded by the triggers, then remove them
+    async fn finish_triggers(&self) -> Result<Vec<ChangeEvent>> {
+        let query = format!(
+            "SELECT id, table_name, operation, CAST(old_row AS CHAR), CAST(new_row AS CHAR) \
+             FROM `{}` ORDER BY id",
+            CHANGELOG_TABLE
+        );
+        let rows = sqlx::query(&query).fetch_all(self.pool()?).await;
+        // Remove the triggers even if the changes can't be read
+        self.drop_capture().await?;
+
+        let mut connection = self.pool()?.acquire().await?;
+        let mut changelog_columns = HashMap::new();
+        let mut events = Vec::new();
+        for row in rows? {
+            let table_name = Self::get_string(&row, 1).unwrap_or_default();
+            if !changelog_columns.contains_key(&table_name) {
+                let columns = self.changelog_columns(&mut connection, &table_name).await?;
+                changelog_columns.insert(table_name.clone(), columns);
+            }
+            let columns = &changelog_columns[&table_name];
+
+            events.push(changelog_event(
+                row.try_get::<i64, _>(0)? as u64,
+                table_name.clone(),
+                &Self::get_string(&row, 2).unwrap_or_default(),
+                Self::changelog_row(Self::get_string(&row, 3), columns, self.timezone)?,
+                Self::changelog_row(Self::get_string(&row, 4), columns, self.timezone)?,
+            )?);
+        }
+
+        Ok(events)
+    }
+
+    /// Convert a row read by `scan_table`, decoding each value by its column type.
+    ///
+    /// Values come out as in binlog row images: integers (signed or unsigned) and
+    /// floats as numbers, DECIMAL as exact numbers, temporal values as text, BIT(1) as a
+    /// boolean and wider BIT as a number, JSON as structured JSON, and binary strings and
+    /// GEOMETRY in their binary format. A value that can't be decoded is an error.
+    fn row_to_hashmap(
+        row: &MySqlRow,
+        columns: &HashMap<String, ColumnInfo>,
+    ) -> Result<BTreeMap<String, Value>> {
+        let mut map = BTreeMap::new();
+
+        for (i, column) in row.columns().iter().enumerate() {
+            let column_name = column.name();
+            let type_name = column.type_info().name();
+
+            let raw_value = row.try_get_raw(i)?;
+            let value = if raw_value.is_null() {
+                Value::Null
+            } else {
+                Self::decode_value(row, i, type_name, columns.get(column_name)).map_err(|e| {
+                    DbbaError::Decode(format!("column `{}` ({}): {}", column_name, type_name, e))
+                })?
             };
 
             map.insert(column_name.to_string(), value);
@@ -100,12 +512,115 @@ impl MySqlAdapter {
 
         Ok(map)
     }
+
+    /// Decode one non-NULL value of a row
+    fn decode_value(
+        row: &MySqlRow,
+        i: usize,
+        type_name: &str,
+        info: Option<&ColumnInfo>,
+    ) -> std::result::Result<Value, sqlx::Error> {
+        let precision = info.map_or(0, |info| info.precision);
+        Ok(match type_name {
+            "BOOLEAN" | "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
+                Value::from(row.try_get::<i64, _>(i)?)
+            }
+            "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+            | "BIGINT UNSIGNED" => Value::from(row.try_get::<u64, _>(i)?),
+            "FLOAT" => float(row.try_get::<f32, _>(i)? as f64),
+            "DOUBLE" => float(row.try_get::<f64, _>(i)?),
+            // Sent as text, so no digit is lost to floating point
+            "DECIMAL" => decimal_value(&row.try_get_unchecked::<String, _>(i)?),
+            "YEAR" => Value::from(row.try_get_unchecked::<u16, _>(i)?),
+            "DATE" => match row.try_get::<NaiveDate, _>(i) {
+                Ok(date) => Value::String(date.format("%Y-%m-%d").to_string()),
+                Err(_) if Self::is_zero_date(row, i) => Value::String("0000-00-00".to_string()),
+                Err(e) => return Err(e),
+            },
+            // TIMESTAMP values are read in UTC, the time zone of the pool's connections
+            "DATETIME" | "TIMESTAMP" => match row.try_get::<NaiveDateTime, _>(i) {
+                Ok(datetime) => {
+                    let datetime = match info {
+                        Some(info) if type_name == "TIMESTAMP" => info.timezone.from_utc(datetime),
+                        _ => datetime,
+                    };
+                    Value::String(format!(
+                        "{}{}",
+                        datetime.format("%Y-%m-%d %H:%M:%S"),
+                        fractional_seconds(datetime.nanosecond() / 1000, precision)
+                    ))
+                }
+                Err(_) if Self::is_zero_date(row, i) => Value::String(format!(
+                    "0000-00-00 00:00:00{}",
+                    fractional_seconds(0, precision)
+                )),
+                Err(e) => return Err(e),
+            },
+            "TIME" => {
+                let time = row.try_get::<MySqlTime, _>(i)?;
+                Value::String(format!(
+                    "{}{:02}:{:02}:{:02}{}",
+                    if time.is_negative() { "-" } else { "" },
+                    time.hours(),
+                    time.minutes(),
+                    time.seconds(),
+                    fractional_seconds(time.microseconds(), precision)
+                ))
+            }
+            "BIT" => {
+                let bits = row.try_get_unchecked::<u64, _>(i)?;
+                if info.is_some_and(|info| info.boolean) {
+                    Value::Bool(bits != 0)
+                } else {
+                    Value::from(bits)
+                }
+            }
+            "JSON" => {
+                let json = row.try_get_unchecked::<String, _>(i)?;
+                serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+            }
+            "ENUM" | "SET" | "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT"
+            | "LONGTEXT" => Value::String(row.try_get_unchecked::<String, _>(i)?),
+            // Text columns with a binary collation are flagged as binary too
+            "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB"
+                if info.is_some_and(|info| !info.binary) =>
+            {
+                Value::String(row.try_get_unchecked::<String, _>(i)?)
+            }
+            "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB"
+            | "GEOMETRY" => {
+                let bytes = row.try_get_unchecked::<Vec<u8>, _>(i)?;
+                info.map_or_else(BinaryFormat::default, |info| info.binary_format)
+                    .render(&bytes)
+            }
+            "NULL" => Value::Null,
+            _ => {
+                return Err(sqlx::Error::Decode(
+                    format!("unsupported column type {}", type_name).into(),
+                ))
+            }
+        })
+    }
+
+    /// Whether a DATE, DATETIME or TIMESTAMP value is MySQL's zero date, which has no
+    /// chrono equivalent
+    fn is_zero_date(row: &MySqlRow, i: usize) -> bool {
+        match row.try_get_unchecked::<&[u8], _>(i) {
+            // Binary protocol: the length prefix of a value whose fields are all zero
+            Ok([0]) => true,
+            Ok(text) => text.starts_with(b"0000-00-00"),
+            Err(_) => false,
+        }
+    }
 }
 
 #[async_trait]
 impl DatabaseAdapter for MySqlAdapter {
     async fn connect(&mut self) -> Result<()> {
-        let pool = MySqlPool::connect(&self.db_info.connection_url()).await?;
+        let pool = MySqlPoolOptions::new()
+            .max_connections(self.max_connections)
+            .connect(&self.db_info.connection_url())
+            .await?;
         self.pool = Some(pool);
         Ok(())
     }
@@ -117,51 +632,488 @@ impl DatabaseAdapter for MySqlAdapter {
         Ok(())
     }
 
-    async fn read_database(&self) -> Result<DatabaseSnapshot> {
+    async fn key_columns(&self, table_name: &str) -> Result<Vec<String>> {
+        let mut connection = self.connection().await?;
+        let query = r#"
+            SELECT k.TABLE_NAME, k.CONSTRAINT_NAME, t.CONSTRAINT_TYPE, k.COLUMN_NAME, c.IS_NULLABLE
+            FROM INFORMATION_SCHEMA.KEY_COLUMN_USAGE k
+            JOIN INFORMATION_SCHEMA.TABLE_CONSTRAINTS t
+              ON t.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA
+             AND t.TABLE_NAME = k.TABLE_NAME
+             AND t.CONSTRAINT_NAME = k.CONSTRAINT_NAME
+            JOIN INFORMATION_SCHEMA.COLUMNS c
+              ON c.TABLE_SCHEMA = k.TABLE_SCHEMA
+             AND c.TABLE_NAME = k.TABLE_NAME
+             AND c.COLUMN_NAME = k.COLUMN_NAME
+            WHERE k.TABLE_SCHEMA = DATABASE()
+              AND k.TABLE_NAME = ?
+              AND t.CONSTRAINT_TYPE IN ('PRIMARY KEY', 'UNIQUE')
+            ORDER BY k.TABLE_NAME, k.CONSTRAINT_NAME, k.ORDINAL_POSITION
+        "#;
+
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut key_columns = Vec::new();
+        for row in rows {
+            let (
+                Some(table_name),
+                Some(constraint_name),
+                Some(constraint_type),
+                Some(column_name),
+                Some(is_nullable),
+            ) = (
+                Self::get_string(&row, 0),
+                Self::get_string(&row, 1),
+                Self::get_string(&row, 2),
+                Self::get_string(&row, 3),
+                Self::get_string(&row, 4),
+            )
+            else {
+                continue;
+            };
+            key_columns.push(KeyColumn {
+                table_name,
+                constraint_name,
+                is_primary: constraint_type == "PRIMARY KEY",
+                column_name,
+                is_nullable: is_nullable == "YES",
+            });
+        }
+
+        Ok(select_table_keys(&key_columns)
+            .remove(table_name)
+            .unwrap_or_default())
+    }
+
+    async fn scan_table(&self, table_name: &str, sink: &mut RowSink<'_>) -> Result<()> {
+        let mut connection = self.connection().await?;
+        let columns = self.table_columns(&mut connection, table_name).await?;
+
+        // Stream the rows so that large tables are never held in memory at once
+        let query = format!("SELECT * FROM `{}`", table_name);
+        let mut rows = sqlx::query(&query).fetch(&mut *connection);
+        while let Some(row) = rows.try_next().await? {
+            sink(Self::row_to_hashmap(&row, &columns)?)?;
+        }
+
+        Ok(())
+    }
+
+    async fn table_checksum(&self, table_name: &str) -> Result<Option<TableChecksum>> {
+        // CHECKSUM TABLE reads the latest committed rows, not those of the transaction
+        // it runs in, so it can't be compared with a consistent snapshot
+        if self.snapshot.is_active() {
+            return Ok(None);
+        }
+        let mut connection = self.connection().await?;
+
+        // CHECKSUM TABLE yields NULL for views and tables it can't checksum
+        let query = format!("CHECKSUM TABLE `{}`", table_name);
+        let row = sqlx::query(&query).fetch_one(&mut *connection).await?;
+        // `Checksum` is a signed BIGINT
+        let checksum: Option<i64> = row.try_get(1)?;
+
+        Ok(checksum.map(|checksum| TableChecksum {
+            row_count: None,
+            checksum: checksum.to_string(),
+        }))
+    }
+
+    async fn table_schema(&self, table_name: &str) -> Result<Option<TableSchema>> {
+        let mut connection = self.connection().await?;
+        let mut schema = TableSchema::default();
+
+        let query = r#"
+            SELECT COLUMN_NAME, CONCAT_WS(' ', COLUMN_TYPE, NULLIF(EXTRA, '')), IS_NULLABLE,
+                   COLUMN_DEFAULT
+            FROM INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+            ORDER BY ORDINAL_POSITION
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let (Some(name), Some(data_type)) =
+                (Self::get_string(&row, 0), Self::get_string(&row, 1))
+            else {
+                continue;
+            };
+            schema.columns.push(ColumnDefinition {
+                name,
+                data_type,
+                nullable: Self::get_string(&row, 2).as_deref() == Some("YES"),
+                default: Self::get_string(&row, 3),
+            });
+        }
+
+        // Functional indexes have an expression instead of a column
+        let query = r#"
+            SELECT INDEX_NAME, IF(NON_UNIQUE = 0, 'YES', 'NO'), COLUMN_NAME
+            FROM INFORMATION_SCHEMA.STATISTICS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+            ORDER BY INDEX_NAME, SEQ_IN_INDEX
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let Some(name) = Self::get_string(&row, 0) else {
+                continue;
+            };
+            schema.push_index_column(
+                &name,
+                name == "PRIMARY",
+                Self::get_string(&row, 1).as_deref() == Some("YES"),
+                Self::get_string(&row, 2).unwrap_or_else(|| "(expression)".to_string()),
+            );
+        }
+
+        let query = r#"
+            SELECT CONSTRAINT_NAME, COLUMN_NAME, REFERENCED_TABLE_NAME, REFERENCED_COLUMN_NAME
+            FROM INFORMATION_SCHEMA.KEY_COLUMN_USAGE
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+              AND REFERENCED_TABLE_NAME IS NOT NULL
+            ORDER BY CONSTRAINT_NAME, ORDINAL_POSITION
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let (Some(name), Some(column), Some(referenced_table)) = (
+                Self::get_string(&row, 0),
+                Self::get_string(&row, 1),
+                Self::get_string(&row, 2),
+            ) else {
+                continue;
+            };
+            schema.push_foreign_key_column(
+                &name,
+                column,
+                referenced_table,
+                Self::get_string(&row, 3),
+            );
+        }
+
+        Ok(Some(schema))
+    }
+
+    async fn begin_snapshot(&self, connections: usize) -> Result<usize> {
         let pool = self.pool()?;
-        let tables = self.list_tables().await?;
+        let connections = connections.clamp(1, pool.options().get_max_connections() as usize);
+        let mut first = pool.acquire().await?;
 
-        let mut snapshot = HashMap::new();
+        // Transactions started while a global read lock blocks all writes see the same
+        // data. Taking the lock needs the RELOAD privilege; without it, or if the lock
+        // is not granted quickly, read everything on a single connection.
+        let locked = connections > 1
+            && (&mut *first)
+                .execute("SET SESSION lock_wait_timeout = 10")
+                .await
+                .is_ok()
+            && (&mut *first)
+                .execute("FLUSH TABLES WITH READ LOCK")
+                .await
+                .is_ok();
 
-        for table_name in tables {
-            // Query all data from the table
-            let query = format!("SELECT * FROM `{}`", table_name);
-            let rows = sqlx::query(&query).fetch_all(pool).await?;
+        let others = if locked { connections - 1 } else { 0 };
+        let started = Self::start_transactions(pool, &mut first, others).await;
+        if locked {
+            // Releasing the global read lock does not end the transactions
+            (&mut *first).execute("UNLOCK TABLES").await?;
+        }
+
+        let mut snapshot_connections = vec![first];
+        snapshot_connections.extend(started?);
+        let count = snapshot_connections.len();
+        self.snapshot.start(snapshot_connections);
+        Ok(count)
+    }
 
-            let mut table_data = Vec::new();
-            for row in rows.iter() {
-                let row_map = Self::row_to_hashmap(row)?;
-                table_data.push(row_map);
+    async fn end_snapshot(&self) -> Result<()> {
+        for mut connection in self.snapshot.finish()? {
+            (&mut *connection).execute("ROLLBACK").await?;
+        }
+        Ok(())
+    }
+
+    async fn start_capture(&self, mode: CaptureMode, table_names: &[String]) -> Result<()> {
+        let capture = match mode {
+            CaptureMode::Binlog => {
+                check_plain_connection(&self.db_info)?;
+                self.check_binlog_format().await?;
+                let (schema, columns) = self.binlog_columns().await?;
+                Capture::Binlog {
+                    start: self.binlog_position().await?,
+                    schema,
+                    columns,
+                }
+            }
+            CaptureMode::Triggers => {
+                // Recorded before installing, so a failure halfway is cleaned up too
+                *self.capture.lock().unwrap() = Some(Capture::Triggers);
+                self.start_triggers(table_names).await?;
+                Capture::Triggers
+            }
+            CaptureMode::Snapshot => {
+                return Err(DbbaError::Config(format!(
+                    "--mode {} is not supported by MySQL",
+                    mode
+                )))
             }
+        };
+        *self.capture.lock().unwrap() = Some(capture);
+        Ok(())
+    }
 
-            snapshot.insert(table_name, table_data);
+    async fn finish_capture(&self) -> Result<Vec<ChangeEvent>> {
+        let capture = self.capture.lock().unwrap().take();
+        let (start, schema, mut columns) = match capture {
+            Some(Capture::Binlog {
+                start,
+                schema,
+                columns,
+            }) => (start, schema, columns),
+            Some(Capture::Triggers) => return self.finish_triggers().await,
+            None => {
+                return Err(DbbaError::Config(
+                    "No change capture in progress".to_string(),
+                ))
+            }
+        };
+        let end = self.binlog_position().await?;
+        // Tables the use case created are only known by their layout now
+        let (_, current_columns) = self.binlog_columns().await?;
+        for (table_name, table_columns) in current_columns {
+            columns.entry(table_name).or_insert(table_columns);
         }
 
-        Ok(snapshot)
+        let mut client = BinlogClient::connect(&self.db_info).await?;
+        client
+            .read_changes(EventDecoder::new(&schema, &columns), &start, &end)
+            .await
+    }
+
+    async fn abort_capture(&self) -> Result<()> {
+        let capture = self.capture.lock().unwrap().take();
+        match capture {
+            Some(Capture::Triggers) => self.drop_capture().await,
+            _ => Ok(()),
+        }
     }
 
     async fn list_tables(&self) -> Result<Vec<String>> {
-        let pool = self.pool()?;
-        let query = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.TABLES WHERE table_schema = ?";
+        let mut connection = self.connection().await?;
+        let query =
+            "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.TABLES WHERE table_schema = DATABASE()";
 
-        let rows = sqlx::query(query)
-            .bind(&self.db_info.database)
-            .fetch_all(pool)
-            .await?;
+        let rows = sqlx::query(query).fetch_all(&mut *connection).await?;
 
         let mut table_names = Vec::new();
         for row in rows {
             // Get TABLE_NAME as either String or Vec<u8>
-            let name = if let Ok(n) = row.try_get::<String, _>(0) {
-                n
-            } else if let Ok(n) = row.try_get::<Vec<u8>, _>(0) {
-                String::from_utf8_lossy(&n).to_string()
-            } else {
-                continue;
-            };
-            table_names.push(name);
+            if let Some(name) = Self::get_string(&row, 0) {
+                table_names.push(name);
+            }
         }
 
         Ok(table_names)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChangeKind;
+    use serde_json::json;
+    use sqlx::{Connection, MySqlConnection};
+
+    /// Read back one row with a column of every type from the server at
+    /// `DBBA_TEST_MYSQL_URL`; skipped when it isn't set
+    #[tokio::test]
+    async fn test_scan_table_decodes_every_column_type() {
+        let Ok(url) = std::env::var("DBBA_TEST_MYSQL_URL") else {
+            eprintln!("skipped: DBBA_TEST_MYSQL_URL is not set");
+            return;
+        };
+        let mut connection = MySqlConnection::connect(&url).await.unwrap();
+        sqlx::raw_sql(
+            "SET SESSION sql_mode = '', time_zone = '+00:00';
+             DROP TABLE IF EXISTS dbba_types;
+             CREATE TABLE dbba_types (
+                 id INT PRIMARY KEY, tiny TINYINT, flag TINYINT(1), big BIGINT UNSIGNED,
+                 amount DECIMAL(20,4), ratio FLOAT, score DOUBLE, day DATE, zero_day DATE,
+                 moment DATETIME, stamp TIMESTAMP NULL, duration TIME, made YEAR,
+                 bit1 BIT(1), bits BIT(10), status ENUM('new','done'), tags SET('a','b'),
+                 doc JSON, name VARCHAR(20), code VARCHAR(20) COLLATE utf8mb4_bin, body TEXT,
+                 payload VARBINARY(10), data BLOB, shape GEOMETRY, missing INT
+             );
+             INSERT INTO dbba_types VALUES (
+                 1, -5, 1, 18446744073709551615, 12345678901234.5678, 1.5, 2.25,
+                 '2024-02-29', '0000-00-00', '2024-01-02 03:04:05', '2024-01-02 03:04:05',
+                 '-838:59:59', 2024, b'1', b'1000000001', 'done', 'a,b', '{\"a\": [1, 2]}',
+                 'John', 'Code', 'text', 'hello', 'hello', ST_GeomFromText('POINT(1 1)'), NULL
+             );",
+        )
+        .execute(&mut connection)
+        .await
+        .unwrap();
+
+        let mut adapter = MySqlAdapter::new(DbInfo::from_url(url).unwrap());
+        adapter.connect().await.unwrap();
+        let table = adapter.read_table("dbba_types").await.unwrap();
+        sqlx::raw_sql("DROP TABLE dbba_types")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+
+        let row = &table.rows[0];
+        let hello = json!("MD5 Digest value: 5d41402abc4b2a76b9719d911017c592");
+        let expected = [
+            ("tiny", json!(-5)),
+            ("flag", json!(1)),
+            ("big", json!(18446744073709551615u64)),
+            ("amount", decimal_value("12345678901234.5678")),
+            ("ratio", json!(1.5)),
+            ("score", json!(2.25)),
+            ("day", json!("2024-02-29")),
+            ("zero_day", json!("0000-00-00")),
+            ("moment", json!("2024-01-02 03:04:05")),
+            ("stamp", json!("2024-01-02 03:04:05")),
+            ("duration", json!("-838:59:59")),
+            ("made", json!(2024)),
+            ("bit1", json!(true)),
+            ("bits", json!(513)),
+            ("status", json!("done")),
+            ("tags", json!("a,b")),
+            ("doc", json!({"a": [1, 2]})),
+            ("name", json!("John")),
+            ("code", json!("Code")),
+            ("body", json!("text")),
+            ("payload", hello.clone()),
+            ("data", hello),
+            ("missing", Value::Null),
+        ];
+        for (column, value) in expected {
+            assert_eq!(row[column], value, "column {}", column);
+        }
+        assert!(row["shape"]
+            .as_str()
+            .unwrap()
+            .starts_with("MD5 Digest value: "));
+    }
+
+    #[test]
+    fn test_changelog_row_decodes_binary_and_timestamps() {
+        let columns = HashMap::from([
+            (
+                "data".to_string(),
+                ChangelogColumn::Binary(BinaryFormat::Hex),
+            ),
+            ("created_at".to_string(), ChangelogColumn::Timestamp(0)),
+            ("updated_at".to_string(), ChangelogColumn::Timestamp(3)),
+        ]);
+        let json = r#"{"id": 1, "data": ["CAFE"], "created_at": 1704164645, "updated_at": 1704164645.120, "deleted_at": null}"#;
+
+        let row = MySqlAdapter::changelog_row(Some(json.to_string()), &columns, Timezone::utc())
+            .unwrap()
+            .unwrap();
+        assert_eq!(row["data"], json!("0xcafe"));
+        assert_eq!(row["created_at"], json!("2024-01-02 03:04:05"));
+        assert_eq!(row["updated_at"], json!("2024-01-02 03:04:05.120"));
+        assert_eq!(row["deleted_at"], Value::Null);
+
+        let tokyo = "+09:00".parse().unwrap();
+        let row = MySqlAdapter::changelog_row(Some(json.to_string()), &columns, tokyo)
+            .unwrap()
+            .unwrap();
+        assert_eq!(row["created_at"], json!("2024-01-02 12:04:05"));
+        assert!(MySqlAdapter::changelog_row(None, &columns, tokyo)
+            .unwrap()
+            .is_none());
+    }
+
+    /// Record a change with triggers through a pool of a single connection on the
+    /// server at `DBBA_TEST_MYSQL_URL`; skipped when it isn't set
+    #[tokio::test]
+    async fn test_triggers_capture_with_one_connection() {
+        let Ok(url) = std::env::var("DBBA_TEST_MYSQL_URL") else {
+            eprintln!("skipped: DBBA_TEST_MYSQL_URL is not set");
+            return;
+        };
+        let mut connection = MySqlConnection::connect(&url).await.unwrap();
+        sqlx::raw_sql(
+            "DROP TABLE IF EXISTS dbba_capture;
+             CREATE TABLE dbba_capture (id INT PRIMARY KEY, name VARCHAR(20));",
+        )
+        .execute(&mut connection)
+        .await
+        .unwrap();
+
+        let mut adapter = MySqlAdapter::new(DbInfo::from_url(url).unwrap()).with_max_connections(1);
+        adapter.connect().await.unwrap();
+        adapter
+            .start_capture(CaptureMode::Triggers, &["dbba_capture".to_string()])
+            .await
+            .unwrap();
+        sqlx::raw_sql("INSERT INTO dbba_capture VALUES (1, 'John')")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+        let events = adapter.finish_capture().await.unwrap();
+        sqlx::raw_sql("DROP TABLE dbba_capture")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ChangeKind::Added);
+        assert_eq!(events[0].after.as_ref().unwrap()["name"], json!("John"));
+    }
+
+    /// Checksum a table with `CHECKSUM TABLE` on the server at `DBBA_TEST_MYSQL_URL`;
+    /// skipped when it isn't set
+    #[tokio::test]
+    async fn test_table_checksum_reads_checksum_table() {
+        let Ok(url) = std::env::var("DBBA_TEST_MYSQL_URL") else {
+            eprintln!("skipped: DBBA_TEST_MYSQL_URL is not set");
+            return;
+        };
+        let mut connection = MySqlConnection::connect(&url).await.unwrap();
+        sqlx::raw_sql(
+            "DROP TABLE IF EXISTS dbba_checksum;
+             CREATE TABLE dbba_checksum (id INT PRIMARY KEY, name VARCHAR(20));
+             INSERT INTO dbba_checksum VALUES (1, 'John'), (2, 'Jane');",
+        )
+        .execute(&mut connection)
+        .await
+        .unwrap();
+
+        let mut adapter = MySqlAdapter::new(DbInfo::from_url(url).unwrap());
+        adapter.connect().await.unwrap();
+        let before = adapter.table_checksum("dbba_checksum").await.unwrap();
+        sqlx::raw_sql("UPDATE dbba_checksum SET name = 'Jim' WHERE id = 2")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+        let after = adapter.table_checksum("dbba_checksum").await.unwrap();
+        adapter.begin_snapshot(1).await.unwrap();
+        let in_snapshot = adapter.table_checksum("dbba_checksum").await.unwrap();
+        adapter.end_snapshot().await.unwrap();
+        sqlx::raw_sql("DROP TABLE dbba_checksum")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+
+        let before = before.expect("CHECKSUM TABLE of a base table");
+        let after = after.expect("CHECKSUM TABLE of a base table");
+        assert_ne!(before, after);
+        assert!(in_snapshot.is_none());
+    }
+}
@@ -0,0 +1,1490 @@
+//! Minimal client for the MySQL replication protocol, used to read the row changes
+//! written to the binary log between two positions.
+//!
+//! Only what dbba needs is implemented: password authentication over a plain TCP
+//! connection, `COM_BINLOG_DUMP`, and decoding of the table map and row events written
+//! with `binlog_format=ROW` and `binlog_row_image=FULL`.
+
+use crate::adapters::database::decimal_value;
+use crate::error::{DbbaError, Result};
+use crate::models::{BinaryFormat, ChangeEvent, ChangeKind, DbInfo, Row, Timezone};
+use chrono::DateTime;
+use md5::Digest;
+use rsa::{pkcs8::DecodePublicKey, Oaep, RsaPublicKey};
+use serde_json::{Map, Number, Value};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+
+// Client capability flags
+const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+const CLIENT_LONG_FLAG: u32 = 0x0000_0004;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_TRANSACTIONS: u32 = 0x0000_2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+
+const COM_QUERY: u8 = 0x03;
+const COM_BINLOG_DUMP: u8 = 0x12;
+/// Ask the server to end the dump at the end of the binary log instead of waiting
+const BINLOG_DUMP_NON_BLOCK: u16 = 0x01;
+const MAX_PACKET_LENGTH: usize = 0xFF_FFFF;
+
+// Binlog event types
+const ROTATE_EVENT: u8 = 4;
+const FORMAT_DESCRIPTION_EVENT: u8 = 15;
+const TABLE_MAP_EVENT: u8 = 19;
+const WRITE_ROWS_EVENT_V1: u8 = 23;
+const UPDATE_ROWS_EVENT_V1: u8 = 24;
+const DELETE_ROWS_EVENT_V1: u8 = 25;
+const WRITE_ROWS_EVENT: u8 = 30;
+const UPDATE_ROWS_EVENT: u8 = 31;
+const DELETE_ROWS_EVENT: u8 = 32;
+const PARTIAL_UPDATE_ROWS_EVENT: u8 = 39;
+
+const EVENT_HEADER_LENGTH: usize = 19;
+const CHECKSUM_LENGTH: usize = 4;
+const CHECKSUM_ALG_CRC32: u8 = 1;
+
+// Column types as written in table map events
+const TYPE_DECIMAL: u8 = 0;
+const TYPE_TINY: u8 = 1;
+const TYPE_SHORT: u8 = 2;
+const TYPE_LONG: u8 = 3;
+const TYPE_FLOAT: u8 = 4;
+const TYPE_DOUBLE: u8 = 5;
+const TYPE_NULL: u8 = 6;
+const TYPE_TIMESTAMP: u8 = 7;
+const TYPE_LONGLONG: u8 = 8;
+const TYPE_INT24: u8 = 9;
+const TYPE_DATE: u8 = 10;
+const TYPE_TIME: u8 = 11;
+const TYPE_DATETIME: u8 = 12;
+const TYPE_YEAR: u8 = 13;
+const TYPE_VARCHAR: u8 = 15;
+const TYPE_BIT: u8 = 16;
+const TYPE_TIMESTAMP2: u8 = 17;
+const TYPE_DATETIME2: u8 = 18;
+const TYPE_TIME2: u8 = 19;
+const TYPE_JSON: u8 = 245;
+const TYPE_NEWDECIMAL: u8 = 246;
+const TYPE_ENUM: u8 = 247;
+const TYPE_SET: u8 = 248;
+const TYPE_BLOB: u8 = 252;
+const TYPE_VAR_STRING: u8 = 253;
+const TYPE_STRING: u8 = 254;
+const TYPE_GEOMETRY: u8 = 255;
+
+/// Position in the binary log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinlogPosition {
+    pub file: String,
+    pub position: u64,
+}
+
+/// Column details needed to decode row images, as found in `INFORMATION_SCHEMA.COLUMNS`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub unsigned: bool,
+    /// Whether string values are binary (BINARY, VARBINARY and BLOB columns)
+    pub binary: bool,
+    /// Whether values are shown as booleans (BIT(1) columns)
+    pub boolean: bool,
+    /// Labels of ENUM and SET columns, in declaration order
+    pub labels: Vec<String>,
+    /// How binary and GEOMETRY values are shown
+    pub binary_format: BinaryFormat,
+    /// Digits of fractional seconds of DATETIME, TIMESTAMP and TIME columns
+    pub precision: usize,
+    /// Time zone TIMESTAMP values are shown in
+    pub timezone: Timezone,
+}
+
+impl ColumnInfo {
+    /// Build column details from `DATA_TYPE` and `COLUMN_TYPE`
+    pub fn new(name: String, data_type: &str, column_type: &str) -> Self {
+        let data_type = data_type.to_ascii_lowercase();
+        let labels = match data_type.as_str() {
+            "enum" | "set" => parse_labels(column_type),
+            _ => Vec::new(),
+        };
+        // e.g. `datetime(6)`
+        let precision = match data_type.as_str() {
+            "datetime" | "timestamp" | "time" => column_type
+                .split_once('(')
+                .and_then(|(_, rest)| rest.strip_suffix(')'))
+                .and_then(|digits| digits.parse().ok())
+                .unwrap_or(0),
+            _ => 0,
+        };
+        Self {
+            name,
+            unsigned: column_type.to_ascii_lowercase().contains("unsigned"),
+            binary: matches!(
+                data_type.as_str(),
+                "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob"
+            ),
+            boolean: column_type.eq_ignore_ascii_case("bit(1)"),
+            labels,
+            binary_format: BinaryFormat::default(),
+            precision,
+            timezone: Timezone::default(),
+        }
+    }
+
+    /// Set how binary and GEOMETRY values are shown
+    pub fn with_binary_format(mut self, binary_format: BinaryFormat) -> Self {
+        self.binary_format = binary_format;
+        self
+    }
+
+    /// Set the time zone TIMESTAMP values are shown in
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+}
+
+/// Parse the labels of `enum('a','b')` or `set('a','b')`
+fn parse_labels(column_type: &str) -> Vec<String> {
+    let Some(list) = column_type
+        .split_once('(')
+        .and_then(|(_, rest)| rest.strip_suffix(')'))
+    else {
+        return Vec::new();
+    };
+
+    let mut labels = Vec::new();
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        let mut label = String::new();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                // A doubled quote is an escaped quote
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            label.push(c);
+        }
+        labels.push(label);
+    }
+    labels
+}
+
+/// Check that `db_info` does not ask for TLS, which the replication connection does
+/// not support
+pub fn check_plain_connection(db_info: &DbInfo) -> Result<()> {
+    let Some(url) = &db_info.url else {
+        return Ok(());
+    };
+    let url = url::Url::parse(url)
+        .map_err(|e| DbbaError::Config(format!("Invalid database URL: {}", e)))?;
+    for (key, value) in url.query_pairs() {
+        let requires_tls = matches!(
+            value.to_ascii_uppercase().replace('-', "_").as_str(),
+            "REQUIRED" | "VERIFY_CA" | "VERIFY_IDENTITY"
+        );
+        if matches!(key.as_ref(), "ssl-mode" | "sslmode") && requires_tls {
+            return Err(DbbaError::Config(format!(
+                "--mode binlog reads the binary log over plain TCP and cannot honor {}={}",
+                key, value
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Connection to a MySQL server speaking the replication protocol
+pub struct BinlogClient {
+    stream: BufStream<TcpStream>,
+    sequence: u8,
+}
+
+impl BinlogClient {
+    /// Connect and authenticate with the credentials of `db_info`
+    pub async fn connect(db_info: &DbInfo) -> Result<Self> {
+        check_plain_connection(db_info)?;
+        let (host, port, username, password) = match &db_info.url {
+            Some(url) => {
+                let url = url::Url::parse(url)
+                    .map_err(|e| DbbaError::Config(format!("Invalid database URL: {}", e)))?;
+                let decode = |s: &str| {
+                    percent_encoding::percent_decode_str(s)
+                        .decode_utf8_lossy()
+                        .to_string()
+                };
+                (
+                    url.host_str().unwrap_or("localhost").to_string(),
+                    url.port().unwrap_or(3306),
+                    decode(url.username()),
+                    decode(url.password().unwrap_or_default()),
+                )
+            }
+            None => (
+                db_info.host.clone(),
+                db_info.port,
+                db_info.username.clone(),
+                db_info.password.clone(),
+            ),
+        };
+
+        let stream = TcpStream::connect((host.as_str(), port)).await?;
+        let mut client = Self {
+            stream: BufStream::new(stream),
+            sequence: 0,
+        };
+        client.authenticate(&username, &password).await?;
+        Ok(client)
+    }
+
+    /// Read the row changes `decoder` keeps between the positions `from` and `to`, in
+    /// the order they were written to the binary log
+    pub async fn read_changes(
+        &mut self,
+        mut decoder: EventDecoder<'_>,
+        from: &BinlogPosition,
+        to: &BinlogPosition,
+    ) -> Result<Vec<ChangeEvent>> {
+        if from == to {
+            return Ok(Vec::new());
+        }
+
+        // Without this the server refuses to send events with checksums
+        self.query("SET @master_binlog_checksum = @@global.binlog_checksum")
+            .await?;
+        self.query("SET @source_binlog_checksum = @@global.binlog_checksum")
+            .await?;
+
+        let position = u32::try_from(from.position).map_err(|_| {
+            DbbaError::Binlog(format!("position {} is out of range", from.position))
+        })?;
+        let mut command = vec![COM_BINLOG_DUMP];
+        command.extend_from_slice(&position.to_le_bytes());
+        command.extend_from_slice(&BINLOG_DUMP_NON_BLOCK.to_le_bytes());
+        command.extend_from_slice(&replica_server_id().to_le_bytes());
+        command.extend_from_slice(from.file.as_bytes());
+        self.sequence = 0;
+        self.write_packet(&command).await?;
+
+        let mut file = from.file.clone();
+        loop {
+            let packet = self.read_packet().await?;
+            match packet.first() {
+                Some(0x00) => {}
+                Some(0xFE) if packet.len() < 9 => break,
+                Some(0xFF) => return Err(server_error(&packet)),
+                _ => return Err(DbbaError::Binlog("unexpected packet in dump".to_string())),
+            }
+
+            let event = &packet[1..];
+            if event.len() < EVENT_HEADER_LENGTH {
+                return Err(DbbaError::Binlog("truncated event header".to_string()));
+            }
+            let event_type = event[4];
+            let log_position = u32::from_le_bytes([event[13], event[14], event[15], event[16]]);
+
+            // Rotations sent at the start of each file carry no position; the rotate
+            // event written at the end of a file names the next one
+            if event_type == ROTATE_EVENT {
+                if log_position != 0 {
+                    let body = decoder.event_body(event)?;
+                    file = String::from_utf8_lossy(body.get(8..).unwrap_or_default()).to_string();
+                }
+                continue;
+            }
+
+            decoder.decode(event)?;
+            if file == to.file && u64::from(log_position) >= to.position {
+                break;
+            }
+        }
+
+        Ok(decoder.finish())
+    }
+
+    /// Run a statement that returns no rows
+    async fn query(&mut self, sql: &str) -> Result<()> {
+        let mut command = vec![COM_QUERY];
+        command.extend_from_slice(sql.as_bytes());
+        self.sequence = 0;
+        self.write_packet(&command).await?;
+
+        let packet = self.read_packet().await?;
+        match packet.first() {
+            Some(0x00) => Ok(()),
+            Some(0xFF) => Err(server_error(&packet)),
+            _ => Err(DbbaError::Binlog(format!(
+                "unexpected response to '{}'",
+                sql
+            ))),
+        }
+    }
+
+    /// Perform the connection handshake
+    async fn authenticate(&mut self, username: &str, password: &str) -> Result<()> {
+        let handshake = self.read_packet().await?;
+        if handshake.first() == Some(&0xFF) {
+            return Err(server_error(&handshake));
+        }
+
+        let mut reader = Reader::new(&handshake);
+        reader.skip(1)?; // protocol version
+        reader.null_terminated()?; // server version
+        reader.skip(4)?; // connection id
+        let mut nonce = reader.bytes(8)?.to_vec();
+        reader.skip(1 + 2 + 1 + 2 + 2)?; // filler, capabilities, charset, status, capabilities
+        let nonce_length = reader.u8()? as usize;
+        reader.skip(10)?;
+        let rest = nonce_length
+            .saturating_sub(8)
+            .max(13)
+            .min(reader.remaining());
+        nonce.extend_from_slice(reader.bytes(rest)?);
+        nonce.truncate(20);
+        let mut plugin = match reader.null_terminated() {
+            Ok(name) if !name.is_empty() => String::from_utf8_lossy(name).to_string(),
+            _ => "mysql_native_password".to_string(),
+        };
+
+        let capabilities = CLIENT_LONG_PASSWORD
+            | CLIENT_LONG_FLAG
+            | CLIENT_PROTOCOL_41
+            | CLIENT_TRANSACTIONS
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH;
+        let auth_response = scramble(&plugin, password, &nonce)?;
+        let mut response = Vec::new();
+        response.extend_from_slice(&capabilities.to_le_bytes());
+        response.extend_from_slice(&(MAX_PACKET_LENGTH as u32 + 1).to_le_bytes());
+        response.push(45); // utf8mb4_general_ci
+        response.extend_from_slice(&[0; 23]);
+        response.extend_from_slice(username.as_bytes());
+        response.push(0);
+        response.push(auth_response.len() as u8);
+        response.extend_from_slice(&auth_response);
+        response.extend_from_slice(plugin.as_bytes());
+        response.push(0);
+        self.write_packet(&response).await?;
+
+        loop {
+            let packet = self.read_packet().await?;
+            match packet.first() {
+                Some(0x00) => return Ok(()),
+                Some(0xFF) => return Err(server_error(&packet)),
+                // The server asks to authenticate with another plugin
+                Some(0xFE) => {
+                    let mut reader = Reader::new(&packet[1..]);
+                    plugin = String::from_utf8_lossy(reader.null_terminated()?).to_string();
+                    nonce = reader.bytes(reader.remaining())?.to_vec();
+                    nonce.truncate(20);
+                    let auth_response = scramble(&plugin, password, &nonce)?;
+                    self.write_packet(&auth_response).await?;
+                }
+                Some(0x01) if plugin == "caching_sha2_password" => match packet.get(1) {
+                    // Fast authentication succeeded, an OK packet follows
+                    Some(0x03) => {}
+                    // Full authentication: send the password encrypted with the
+                    // server's public key
+                    Some(0x04) => {
+                        self.write_packet(&[0x02]).await?;
+                        let key = self.read_packet().await?;
+                        let encrypted =
+                            encrypt_password(key.get(1..).unwrap_or_default(), password, &nonce)?;
+                        self.write_packet(&encrypted).await?;
+                    }
+                    _ => {
+                        return Err(DbbaError::Binlog(
+                            "unexpected caching_sha2_password response".to_string(),
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(DbbaError::Binlog(format!(
+                        "unexpected response during authentication with {}",
+                        plugin
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Read one packet, joining packets split at the maximum packet length
+    async fn read_packet(&mut self) -> Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        loop {
+            let mut header = [0u8; 4];
+            self.stream.read_exact(&mut header).await?;
+            let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            self.sequence = header[3].wrapping_add(1);
+
+            let start = payload.len();
+            payload.resize(start + length, 0);
+            self.stream.read_exact(&mut payload[start..]).await?;
+            if length < MAX_PACKET_LENGTH {
+                return Ok(payload);
+            }
+        }
+    }
+
+    async fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+        let length = payload.len() as u32;
+        let mut header = length.to_le_bytes();
+        header[3] = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.stream.write_all(&header).await?;
+        self.stream.write_all(payload).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+}
+
+/// Server id announced to the primary; must differ from the ids of real replicas
+fn replica_server_id() -> u32 {
+    0x7F00_0000 | (std::process::id() & 0x00FF_FFFF)
+}
+
+/// Convert an ERR packet to an error
+fn server_error(packet: &[u8]) -> DbbaError {
+    let code = packet
+        .get(1..3)
+        .map_or(0, |code| u16::from_le_bytes([code[0], code[1]]));
+    let mut message = packet.get(3..).unwrap_or_default();
+    if message.first() == Some(&b'#') {
+        message = message.get(6..).unwrap_or_default();
+    }
+    DbbaError::Binlog(format!(
+        "{} (error {})",
+        String::from_utf8_lossy(message),
+        code
+    ))
+}
+
+/// Compute the authentication response of a password plugin
+fn scramble(plugin: &str, password: &str, nonce: &[u8]) -> Result<Vec<u8>> {
+    if password.is_empty() {
+        return Ok(Vec::new());
+    }
+    match plugin {
+        // SHA1(password) XOR SHA1(nonce + SHA1(SHA1(password)))
+        "mysql_native_password" => {
+            let hash = Sha1::digest(password.as_bytes());
+            let double_hash = Sha1::digest(hash);
+            let seeded = Sha1::new()
+                .chain_update(nonce)
+                .chain_update(double_hash)
+                .finalize();
+            Ok(xor(&hash, &seeded))
+        }
+        // SHA256(password) XOR SHA256(SHA256(SHA256(password)) + nonce)
+        "caching_sha2_password" => {
+            let hash = Sha256::digest(password.as_bytes());
+            let double_hash = Sha256::digest(hash);
+            let seeded = Sha256::new()
+                .chain_update(double_hash)
+                .chain_update(nonce)
+                .finalize();
+            Ok(xor(&hash, &seeded))
+        }
+        _ => Err(DbbaError::Binlog(format!(
+            "authentication plugin {} is not supported",
+            plugin
+        ))),
+    }
+}
+
+/// Encrypt the password for caching_sha2_password full authentication
+fn encrypt_password(public_key: &[u8], password: &str, nonce: &[u8]) -> Result<Vec<u8>> {
+    let pem = String::from_utf8_lossy(public_key);
+    let key = RsaPublicKey::from_public_key_pem(&pem)
+        .map_err(|e| DbbaError::Binlog(format!("invalid server public key: {}", e)))?;
+    let mut password = password.as_bytes().to_vec();
+    password.push(0);
+    let password = xor(&password, nonce);
+    key.encrypt(&mut rand::thread_rng(), Oaep::new::<Sha1>(), &password)
+        .map_err(|e| DbbaError::Binlog(format!("failed to encrypt password: {}", e)))
+}
+
+/// XOR `data` with `key`, repeating the key as needed
+fn xor(data: &[u8], key: &[u8]) -> Vec<u8> {
+    data.iter()
+        .zip(key.iter().cycle())
+        .map(|(a, b)| a ^ b)
+        .collect()
+}
+
+/// Table definition from a table map event
+#[derive(Debug, Clone)]
+struct TableMap {
+    table_name: String,
+    column_types: Vec<u8>,
+    column_meta: Vec<u16>,
+}
+
+/// Decodes binlog events into the row changes of one schema
+pub struct EventDecoder<'a> {
+    schema: &'a str,
+    columns: &'a HashMap<String, Vec<ColumnInfo>>,
+    checksum: bool,
+    tables: HashMap<u64, TableMap>,
+    changes: Vec<ChangeEvent>,
+}
+
+impl<'a> EventDecoder<'a> {
+    /// Decode changes of tables in `schema`, whose columns are described by `columns`
+    /// (by table name)
+    pub fn new(schema: &'a str, columns: &'a HashMap<String, Vec<ColumnInfo>>) -> Self {
+        Self {
+            schema,
+            columns,
+            checksum: false,
+            tables: HashMap::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Row changes decoded so far
+    pub fn finish(self) -> Vec<ChangeEvent> {
+        self.changes
+    }
+
+    /// Body of an event, without its header and checksum
+    fn event_body<'e>(&self, event: &'e [u8]) -> Result<&'e [u8]> {
+        let end = if self.checksum {
+            event.len().saturating_sub(CHECKSUM_LENGTH)
+        } else {
+            event.len()
+        };
+        event
+            .get(EVENT_HEADER_LENGTH..end)
+            .ok_or_else(|| DbbaError::Binlog("truncated event".to_string()))
+    }
+
+    /// Decode one event, including its header
+    pub fn decode(&mut self, event: &[u8]) -> Result<()> {
+        let event_type = event[4];
+        match event_type {
+            FORMAT_DESCRIPTION_EVENT => {
+                // The checksum algorithm precedes the event's own checksum
+                let body = &event[EVENT_HEADER_LENGTH..];
+                self.checksum = body.len() > CHECKSUM_LENGTH
+                    && body[body.len() - CHECKSUM_LENGTH - 1] == CHECKSUM_ALG_CRC32;
+            }
+            TABLE_MAP_EVENT => {
+                let body = self.event_body(event)?;
+                self.decode_table_map(body)?;
+            }
+            WRITE_ROWS_EVENT_V1 | WRITE_ROWS_EVENT => {
+                let body = self.event_body(event)?;
+                self.decode_rows(body, ChangeKind::Added, event_type == WRITE_ROWS_EVENT)?;
+            }
+            UPDATE_ROWS_EVENT_V1 | UPDATE_ROWS_EVENT => {
+                let body = self.event_body(event)?;
+                self.decode_rows(body, ChangeKind::Modified, event_type == UPDATE_ROWS_EVENT)?;
+            }
+            DELETE_ROWS_EVENT_V1 | DELETE_ROWS_EVENT => {
+                let body = self.event_body(event)?;
+                self.decode_rows(body, ChangeKind::Deleted, event_type == DELETE_ROWS_EVENT)?;
+            }
+            PARTIAL_UPDATE_ROWS_EVENT => {
+                return Err(DbbaError::Binlog(
+                    "partial JSON updates are not supported (set binlog_row_value_options to '')"
+                        .to_string(),
+                ))
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn decode_table_map(&mut self, body: &[u8]) -> Result<()> {
+        let mut reader = Reader::new(body);
+        let table_id = reader.uint_le(6)?;
+        reader.skip(2)?; // flags
+        let schema_length = reader.u8()? as usize;
+        let schema = reader.bytes(schema_length)?;
+        reader.skip(1)?;
+        let table_length = reader.u8()? as usize;
+        let table_name = String::from_utf8_lossy(reader.bytes(table_length)?).to_string();
+        reader.skip(1)?;
+
+        // Only tables of the compared database are kept, so their row events are decoded
+        if schema != self.schema.as_bytes() {
+            self.tables.remove(&table_id);
+            return Ok(());
+        }
+
+        let column_count = reader.length_encoded()? as usize;
+        let column_types = reader.bytes(column_count)?.to_vec();
+        let meta_length = reader.length_encoded()? as usize;
+        let mut meta = Reader::new(reader.bytes(meta_length)?);
+        let mut column_meta = Vec::with_capacity(column_count);
+        for &column_type in &column_types {
+            column_meta.push(match column_type {
+                TYPE_STRING | TYPE_NEWDECIMAL => meta.uint_be(2)? as u16,
+                TYPE_VAR_STRING | TYPE_VARCHAR | TYPE_BIT => meta.uint_le(2)? as u16,
+                TYPE_BLOB | TYPE_DOUBLE | TYPE_FLOAT | TYPE_GEOMETRY | TYPE_JSON | TYPE_TIME2
+                | TYPE_DATETIME2 | TYPE_TIMESTAMP2 => meta.u8()? as u16,
+                _ => 0,
+            });
+        }
+
+        self.tables.insert(
+            table_id,
+            TableMap {
+                table_name,
+                column_types,
+                column_meta,
+            },
+        );
+        Ok(())
+    }
+
+    fn decode_rows(&mut self, body: &[u8], kind: ChangeKind, version_2: bool) -> Result<()> {
+        let mut reader = Reader::new(body);
+        let table_id = reader.uint_le(6)?;
+        reader.skip(2)?; // flags
+        if version_2 {
+            let extra_length = reader.uint_le(2)? as usize;
+            reader.skip(extra_length.saturating_sub(2))?;
+        }
+        let Some(table) = self.tables.get(&table_id) else {
+            return Ok(());
+        };
+
+        let column_count = reader.length_encoded()? as usize;
+        let bitmap_length = column_count.div_ceil(8);
+        let present = reader.bytes(bitmap_length)?;
+        let present_after = if kind == ChangeKind::Modified {
+            reader.bytes(bitmap_length)?
+        } else {
+            present
+        };
+
+        let columns = self
+            .columns
+            .get(&table.table_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        while reader.remaining() > 0 {
+            let (before, after) = match kind {
+                ChangeKind::Added => (
+                    None,
+                    Some(read_image(&mut reader, table, columns, present)?),
+                ),
+                ChangeKind::Deleted => (
+                    Some(read_image(&mut reader, table, columns, present)?),
+                    None,
+                ),
+                ChangeKind::Modified => (
+                    Some(read_image(&mut reader, table, columns, present)?),
+                    Some(read_image(&mut reader, table, columns, present_after)?),
+                ),
+            };
+            self.changes.push(ChangeEvent {
+                sequence: self.changes.len() as u64 + 1,
+                table_name: table.table_name.clone(),
+                kind,
+                before,
+                after,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether bit `index` of a bitmap is set
+fn bit(bitmap: &[u8], index: usize) -> bool {
+    bitmap[index / 8] & (1 << (index % 8)) != 0
+}
+
+/// Read one row image with the columns marked in `present`
+fn read_image(
+    reader: &mut Reader,
+    table: &TableMap,
+    columns: &[ColumnInfo],
+    present: &[u8],
+) -> Result<Row> {
+    let column_count = table.column_types.len();
+    let present_count = (0..column_count).filter(|&i| bit(present, i)).count();
+    let nulls = reader.bytes(present_count.div_ceil(8))?;
+
+    let mut row = Row::new();
+    let mut null_index = 0;
+    for index in 0..column_count {
+        if !bit(present, index) {
+            continue;
+        }
+        let is_null = bit(nulls, null_index);
+        null_index += 1;
+
+        // Without column details (e.g. a dropped table) columns are named by position
+        let default_info;
+        let info = match columns.get(index) {
+            Some(info) if columns.len() == column_count => info,
+            _ => {
+                default_info = ColumnInfo {
+                    name: format!("@{}", index + 1),
+                    ..ColumnInfo::default()
+                };
+                &default_info
+            }
+        };
+        let value = if is_null {
+            Value::Null
+        } else {
+            decode_value(
+                reader,
+                table.column_types[index],
+                table.column_meta[index],
+                info,
+            )?
+        };
+        row.insert(info.name.clone(), value);
+    }
+    Ok(row)
+}
+
+/// Decode one column value of a row image
+fn decode_value(
+    reader: &mut Reader,
+    column_type: u8,
+    meta: u16,
+    info: &ColumnInfo,
+) -> Result<Value> {
+    // CHAR, ENUM and SET columns are all written as STRING with the real type in the
+    // metadata
+    let (column_type, length) = if column_type == TYPE_STRING && meta >= 256 {
+        let (byte0, byte1) = ((meta >> 8) as u8, (meta & 0xFF) as usize);
+        if byte0 & 0x30 != 0x30 {
+            (
+                byte0 | 0x30,
+                byte1 | ((((byte0 & 0x30) ^ 0x30) as usize) << 4),
+            )
+        } else {
+            (byte0, byte1)
+        }
+    } else {
+        (column_type, meta as usize)
+    };
+
+    let integer = |reader: &mut Reader, bytes: usize| -> Result<Value> {
+        let value = reader.uint_le(bytes)?;
+        if info.unsigned {
+            return Ok(Value::from(value));
+        }
+        let shift = 64 - bytes * 8;
+        Ok(Value::from(((value << shift) as i64) >> shift))
+    };
+
+    Ok(match column_type {
+        TYPE_NULL => Value::Null,
+        TYPE_TINY => integer(reader, 1)?,
+        TYPE_SHORT => integer(reader, 2)?,
+        TYPE_INT24 => integer(reader, 3)?,
+        TYPE_LONG => integer(reader, 4)?,
+        TYPE_LONGLONG => integer(reader, 8)?,
+        TYPE_FLOAT => {
+            let value = f32::from_le_bytes(reader.array()?);
+            float(value as f64)
+        }
+        TYPE_DOUBLE => float(f64::from_le_bytes(reader.array()?)),
+        TYPE_NEWDECIMAL => {
+            let decimal = decode_decimal(reader, (meta >> 8) as usize, (meta & 0xFF) as usize)?;
+            decimal_value(&decimal)
+        }
+        TYPE_YEAR => match reader.u8()? {
+            0 => Value::from(0),
+            year => Value::from(1900 + year as u32),
+        },
+        TYPE_DATE => {
+            let value = reader.uint_le(3)?;
+            Value::String(format!(
+                "{:04}-{:02}-{:02}",
+                value >> 9,
+                (value >> 5) & 0x0F,
+                value & 0x1F
+            ))
+        }
+        TYPE_TIME => {
+            let value = reader.uint_le(3)?;
+            Value::String(format!(
+                "{:02}:{:02}:{:02}",
+                value / 10000,
+                value / 100 % 100,
+                value % 100
+            ))
+        }
+        TYPE_TIME2 => Value::String(decode_time2(reader, meta as usize)?),
+        TYPE_DATETIME => {
+            let value = reader.uint_le(8)?;
+            let (date, time) = (value / 1_000_000, value % 1_000_000);
+            Value::String(format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                date / 10000,
+                date / 100 % 100,
+                date % 100,
+                time / 10000,
+                time / 100 % 100,
+                time % 100
+            ))
+        }
+        TYPE_DATETIME2 => {
+            let packed = reader.uint_be(5)? as i64 - 0x80_0000_0000;
+            let microseconds = decode_fraction(reader, meta as usize)?;
+            let date = packed >> 17;
+            let (year_month, day) = (date >> 5, date % 32);
+            let time = packed % (1 << 17);
+            Value::String(format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                year_month / 13,
+                year_month % 13,
+                day,
+                time >> 12,
+                (time >> 6) % 64,
+                time % 64,
+                fractional_seconds(microseconds, meta as usize)
+            ))
+        }
+        TYPE_TIMESTAMP | TYPE_TIMESTAMP2 => {
+            let (seconds, microseconds, precision) = if column_type == TYPE_TIMESTAMP {
+                (reader.uint_le(4)?, 0, 0)
+            } else {
+                let seconds = reader.uint_be(4)?;
+                let precision = meta as usize;
+                (seconds, decode_fraction(reader, precision)?, precision)
+            };
+            timestamp_value(seconds as i64, microseconds, precision, info.timezone)
+        }
+        TYPE_BIT => {
+            let bits = (meta >> 8) as usize * 8 + (meta & 0xFF) as usize;
+            let value = reader.uint_be(bits.div_ceil(8))?;
+            if bits == 1 {
+                Value::Bool(value != 0)
+            } else {
+                Value::from(value)
+            }
+        }
+        TYPE_ENUM => {
+            let index = reader.uint_le(length)? as usize;
+            match index.checked_sub(1).and_then(|i| info.labels.get(i)) {
+                Some(label) => Value::String(label.clone()),
+                None if index == 0 => Value::String(String::new()),
+                None => Value::from(index),
+            }
+        }
+        TYPE_SET => {
+            let bits = reader.uint_le(length)?;
+            let labels: Vec<_> = (0..64)
+                .filter(|i| bits & (1 << i) != 0)
+                .map(|i| info.labels.get(i).cloned().unwrap_or_else(|| i.to_string()))
+                .collect();
+            Value::String(labels.join(","))
+        }
+        TYPE_VARCHAR | TYPE_VAR_STRING | TYPE_STRING => {
+            let prefix = if length < 256 { 1 } else { 2 };
+            let value_length = reader.uint_le(prefix)? as usize;
+            string(reader.bytes(value_length)?, info)
+        }
+        TYPE_BLOB | TYPE_GEOMETRY | TYPE_JSON => {
+            let value_length = reader.uint_le(meta as usize)? as usize;
+            let data = reader.bytes(value_length)?;
+            match column_type {
+                TYPE_JSON => decode_json(data)?,
+                TYPE_GEOMETRY => info.binary_format.render(data),
+                _ => string(data, info),
+            }
+        }
+        TYPE_DECIMAL => {
+            return Err(DbbaError::Binlog(
+                "pre-5.0 DECIMAL columns are not supported".to_string(),
+            ))
+        }
+        _ => {
+            return Err(DbbaError::Binlog(format!(
+                "unsupported column type {} for {}",
+                column_type, info.name
+            )))
+        }
+    })
+}
+
+/// A float as a JSON number, or null if it is not finite
+pub(crate) fn float(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+/// A string value, shown in the column's binary format when binary (as in snapshots)
+fn string(data: &[u8], info: &ColumnInfo) -> Value {
+    if info.binary {
+        info.binary_format.render(data)
+    } else {
+        Value::String(String::from_utf8_lossy(data).to_string())
+    }
+}
+
+/// Fractional seconds shown with the column's `precision` digits, e.g. `.123` (empty
+/// without any)
+pub(crate) fn fractional_seconds(microseconds: u32, precision: usize) -> String {
+    if precision == 0 {
+        return String::new();
+    }
+    let digits = format!("{:06}", microseconds);
+    format!(".{}", &digits[..precision.min(6)])
+}
+
+/// A TIMESTAMP value `seconds` after the epoch, shown in `timezone` with `precision`
+/// digits of fractional seconds; zero is the zero timestamp
+pub(crate) fn timestamp_value(
+    seconds: i64,
+    microseconds: u32,
+    precision: usize,
+    timezone: Timezone,
+) -> Value {
+    let fraction = fractional_seconds(microseconds, precision);
+    match DateTime::from_timestamp(seconds, microseconds * 1000) {
+        Some(_) if seconds == 0 => Value::String(format!("0000-00-00 00:00:00{}", fraction)),
+        Some(datetime) => Value::String(format!(
+            "{}{}",
+            timezone
+                .from_utc(datetime.naive_utc())
+                .format("%Y-%m-%d %H:%M:%S"),
+            fraction
+        )),
+        None => Value::Null,
+    }
+}
+
+/// Decode the fractional seconds of a DATETIME2 or TIMESTAMP2 value with `precision`
+/// digits, as microseconds
+fn decode_fraction(reader: &mut Reader, precision: usize) -> Result<u32> {
+    let fraction = reader.uint_be(precision.div_ceil(2))? as u32;
+    Ok(match precision {
+        0 => 0,
+        1 | 2 => fraction * 10000,
+        3 | 4 => fraction * 100,
+        _ => fraction,
+    })
+}
+
+/// Decode a TIME2 value as `[-]HH:MM:SS` with `precision` digits of fractional seconds
+fn decode_time2(reader: &mut Reader, precision: usize) -> Result<String> {
+    const INT_OFFSET: i64 = 0x80_0000;
+    let packed = match precision {
+        0 => (reader.uint_be(3)? as i64 - INT_OFFSET) << 24,
+        1 | 2 => {
+            let mut int_part = reader.uint_be(3)? as i64 - INT_OFFSET;
+            let mut fraction = reader.u8()? as i8 as i64;
+            if int_part < 0 && fraction != 0 {
+                int_part += 1;
+                fraction -= 0x100;
+            }
+            (int_part << 24) + fraction * 10000
+        }
+        3 | 4 => {
+            let mut int_part = reader.uint_be(3)? as i64 - INT_OFFSET;
+            let mut fraction = reader.uint_be(2)? as i64;
+            if int_part < 0 && fraction != 0 {
+                int_part += 1;
+                fraction -= 0x10000;
+            }
+            (int_part << 24) + fraction * 100
+        }
+        _ => reader.uint_be(6)? as i64 - 0x8000_0000_0000,
+    };
+
+    let sign = if packed < 0 { "-" } else { "" };
+    let time = packed.abs() >> 24;
+    let microseconds = (packed.abs() % (1 << 24)) as u32;
+    Ok(format!(
+        "{}{:02}:{:02}:{:02}{}",
+        sign,
+        (time >> 12) % (1 << 10),
+        (time >> 6) % 64,
+        time % 64,
+        fractional_seconds(microseconds, precision)
+    ))
+}
+
+/// Decode a DECIMAL value as an exact decimal string
+fn decode_decimal(reader: &mut Reader, precision: usize, scale: usize) -> Result<String> {
+    const DIGITS_PER_GROUP: usize = 9;
+    const BYTES_FOR_DIGITS: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+    let integral = precision - scale;
+    let (integral_groups, integral_rest) =
+        (integral / DIGITS_PER_GROUP, integral % DIGITS_PER_GROUP);
+    let (fraction_groups, fraction_rest) = (scale / DIGITS_PER_GROUP, scale % DIGITS_PER_GROUP);
+    let size = integral_groups * 4
+        + BYTES_FOR_DIGITS[integral_rest]
+        + fraction_groups * 4
+        + BYTES_FOR_DIGITS[fraction_rest];
+
+    // The sign is stored inverted in the top bit, and negative values have all their
+    // bits inverted
+    let mut data = reader.bytes(size)?.to_vec();
+    let negative = data.first().is_some_and(|byte| byte & 0x80 == 0);
+    if let Some(first) = data.first_mut() {
+        *first ^= 0x80;
+    }
+    if negative {
+        data.iter_mut().for_each(|byte| *byte = !*byte);
+    }
+
+    let mut digits = Reader::new(&data);
+    let mut integer_part = String::new();
+    if integral_rest > 0 {
+        let value = digits.uint_be(BYTES_FOR_DIGITS[integral_rest])?;
+        integer_part.push_str(&value.to_string());
+    }
+    for _ in 0..integral_groups {
+        integer_part.push_str(&format!("{:09}", digits.uint_be(4)?));
+    }
+    let integer_part = integer_part.trim_start_matches('0');
+
+    let mut fraction_part = String::new();
+    for _ in 0..fraction_groups {
+        fraction_part.push_str(&format!("{:09}", digits.uint_be(4)?));
+    }
+    if fraction_rest > 0 {
+        let value = digits.uint_be(BYTES_FOR_DIGITS[fraction_rest])?;
+        fraction_part.push_str(&format!("{:0width$}", value, width = fraction_rest));
+    }
+
+    let mut decimal = String::new();
+    if negative {
+        decimal.push('-');
+    }
+    decimal.push_str(if integer_part.is_empty() {
+        "0"
+    } else {
+        integer_part
+    });
+    if !fraction_part.is_empty() {
+        decimal.push('.');
+        decimal.push_str(&fraction_part);
+    }
+    Ok(decimal)
+}
+
+/// Decode a JSON column value from MySQL's binary JSON format
+fn decode_json(data: &[u8]) -> Result<Value> {
+    match data.split_first() {
+        Some((&value_type, value)) => json_value(value_type, value),
+        None => Ok(Value::Null),
+    }
+}
+
+fn json_value(value_type: u8, data: &[u8]) -> Result<Value> {
+    let mut reader = Reader::new(data);
+    Ok(match value_type {
+        0x00 => json_container(data, false, true)?,
+        0x01 => json_container(data, true, true)?,
+        0x02 => json_container(data, false, false)?,
+        0x03 => json_container(data, true, false)?,
+        0x04 => match reader.u8()? {
+            1 => Value::Bool(true),
+            2 => Value::Bool(false),
+            _ => Value::Null,
+        },
+        0x05 => Value::from(reader.uint_le(2)? as u16 as i16),
+        0x06 => Value::from(reader.uint_le(2)?),
+        0x07 => Value::from(reader.uint_le(4)? as u32 as i32),
+        0x08 => Value::from(reader.uint_le(4)?),
+        0x09 => Value::from(reader.uint_le(8)? as i64),
+        0x0A => Value::from(reader.uint_le(8)?),
+        0x0B => float(f64::from_le_bytes(reader.array()?)),
+        0x0C => {
+            let length = reader.variable_length()?;
+            Value::String(String::from_utf8_lossy(reader.bytes(length)?).to_string())
+        }
+        // Opaque values keep their MySQL type, e.g. DECIMAL or DATETIME
+        0x0F => {
+            let field_type = reader.u8()?;
+            let length = reader.variable_length()?;
+            let mut opaque = Reader::new(reader.bytes(length)?);
+            if field_type == TYPE_NEWDECIMAL && length >= 2 {
+                let precision = opaque.u8()? as usize;
+                let scale = opaque.u8()? as usize;
+                let decimal = decode_decimal(&mut opaque, precision, scale)?;
+                decimal_value(&decimal)
+            } else {
+                Value::String(
+                    String::from_utf8_lossy(opaque.bytes(opaque.remaining())?).to_string(),
+                )
+            }
+        }
+        _ => {
+            return Err(DbbaError::Binlog(format!(
+                "unknown JSON value type {}",
+                value_type
+            )))
+        }
+    })
+}
+
+/// Decode a JSON object or array; offsets are relative to the start of `data`
+fn json_container(data: &[u8], large: bool, object: bool) -> Result<Value> {
+    let offset_size = if large { 4 } else { 2 };
+    let mut reader = Reader::new(data);
+    let count = reader.uint_le(offset_size)? as usize;
+    reader.skip(offset_size)?; // size in bytes
+
+    let mut keys = Vec::new();
+    if object {
+        for _ in 0..count {
+            let offset = reader.uint_le(offset_size)? as usize;
+            let length = reader.uint_le(2)? as usize;
+            let key = data
+                .get(offset..offset + length)
+                .ok_or_else(|| DbbaError::Binlog("JSON key out of range".to_string()))?;
+            keys.push(String::from_utf8_lossy(key).to_string());
+        }
+    }
+
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value_type = reader.u8()?;
+        let entry = reader.bytes(offset_size)?;
+        // Small scalars are stored inline in the value entry
+        let inline =
+            matches!(value_type, 0x04..=0x06) || (large && matches!(value_type, 0x07 | 0x08));
+        let value = if inline {
+            json_value(value_type, entry)?
+        } else {
+            let offset = Reader::new(entry).uint_le(offset_size)? as usize;
+            let value = data
+                .get(offset..)
+                .ok_or_else(|| DbbaError::Binlog("JSON value out of range".to_string()))?;
+            json_value(value_type, value)?
+        };
+        values.push(value);
+    }
+
+    Ok(if object {
+        Value::Object(keys.into_iter().zip(values).collect::<Map<_, _>>())
+    } else {
+        Value::Array(values)
+    })
+}
+
+/// Reads little-endian protocol values from a byte slice
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(|| DbbaError::Binlog("unexpected end of data".to_string()))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<()> {
+        self.bytes(length).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn uint_le(&mut self, length: usize) -> Result<u64> {
+        Ok(self
+            .bytes(length)?
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | byte as u64))
+    }
+
+    fn uint_be(&mut self, length: usize) -> Result<u64> {
+        Ok(self
+            .bytes(length)?
+            .iter()
+            .fold(0, |value, &byte| value << 8 | byte as u64))
+    }
+
+    /// Length-encoded integer of the client/server protocol
+    fn length_encoded(&mut self) -> Result<u64> {
+        match self.u8()? {
+            0xFC => self.uint_le(2),
+            0xFD => self.uint_le(3),
+            0xFE => self.uint_le(8),
+            value => Ok(value as u64),
+        }
+    }
+
+    /// Variable-length integer of the binary JSON format (7 bits per byte)
+    fn variable_length(&mut self) -> Result<usize> {
+        let mut length = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            length |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(length);
+            }
+        }
+        Err(DbbaError::Binlog("invalid JSON length".to_string()))
+    }
+
+    fn null_terminated(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        let length = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| DbbaError::Binlog("unterminated string".to_string()))?;
+        self.position += length + 1;
+        Ok(&rest[..length])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Build an event with a header and a (zero) CRC32 checksum
+    fn event(event_type: u8, body: &[u8]) -> Vec<u8> {
+        let size = (EVENT_HEADER_LENGTH + body.len() + CHECKSUM_LENGTH) as u32;
+        let mut event = vec![0; 4];
+        event.push(event_type);
+        event.extend_from_slice(&1u32.to_le_bytes());
+        event.extend_from_slice(&size.to_le_bytes());
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.extend_from_slice(&0u16.to_le_bytes());
+        event.extend_from_slice(body);
+        event.extend_from_slice(&[0; CHECKSUM_LENGTH]);
+        event
+    }
+
+    fn format_description() -> Vec<u8> {
+        let mut body = vec![4, 0];
+        body.extend_from_slice(&[0; 50 + 4]);
+        body.push(EVENT_HEADER_LENGTH as u8);
+        body.push(CHECKSUM_ALG_CRC32);
+        event(FORMAT_DESCRIPTION_EVENT, &body)
+    }
+
+    fn table_map(table_id: u8, schema: &str, table: &str) -> Vec<u8> {
+        let mut body = vec![table_id, 0, 0, 0, 0, 0, 0, 0];
+        body.push(schema.len() as u8);
+        body.extend_from_slice(schema.as_bytes());
+        body.push(0);
+        body.push(table.len() as u8);
+        body.extend_from_slice(table.as_bytes());
+        body.push(0);
+        // id INT, name VARCHAR(20), created_at DATETIME
+        body.extend_from_slice(&[3, TYPE_LONG, TYPE_VARCHAR, TYPE_DATETIME2]);
+        body.extend_from_slice(&[3, 20, 0, 0]);
+        body.push(0b110);
+        body
+    }
+
+    fn rows_header(table_id: u8, bitmaps: usize) -> Vec<u8> {
+        let mut body = vec![table_id, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3];
+        body.extend(std::iter::repeat_n(0b111, bitmaps));
+        body
+    }
+
+    fn image(id: i32, name: Option<&str>) -> Vec<u8> {
+        let mut image = vec![if name.is_none() { 0b010 } else { 0 }];
+        image.extend_from_slice(&id.to_le_bytes());
+        if let Some(name) = name {
+            image.push(name.len() as u8);
+            image.extend_from_slice(name.as_bytes());
+        }
+        // 2024-01-02 03:04:05
+        let date = ((2024 * 13 + 1) << 5) | 2;
+        let time = (3 << 12) | (4 << 6) | 5;
+        let packed: u64 = ((date << 17) | time) + 0x80_0000_0000;
+        image.extend_from_slice(&packed.to_be_bytes()[3..]);
+        image
+    }
+
+    fn columns() -> HashMap<String, Vec<ColumnInfo>> {
+        let columns = vec![
+            ColumnInfo::new("id".to_string(), "int", "int"),
+            ColumnInfo::new("name".to_string(), "varchar", "varchar(20)"),
+            ColumnInfo::new("created_at".to_string(), "datetime", "datetime"),
+        ];
+        HashMap::from([("users".to_string(), columns)])
+    }
+
+    #[test]
+    fn test_decodes_row_events_of_schema() {
+        let columns = columns();
+        let mut decoder = EventDecoder::new("app", &columns);
+
+        let mut insert = rows_header(42, 1);
+        insert.extend(image(1, Some("John")));
+        insert.extend(image(-2, Some("Jane")));
+        let mut update = rows_header(42, 2);
+        update.extend(image(1, Some("John")));
+        update.extend(image(1, None));
+        let mut delete = rows_header(43, 1);
+        delete.extend(image(1, Some("John")));
+
+        for event in [
+            format_description(),
+            event(TABLE_MAP_EVENT, &table_map(42, "app", "users")),
+            event(WRITE_ROWS_EVENT, &insert),
+            event(UPDATE_ROWS_EVENT, &update),
+            event(TABLE_MAP_EVENT, &table_map(43, "other", "users")),
+            event(DELETE_ROWS_EVENT, &delete),
+        ] {
+            decoder.decode(&event).unwrap();
+        }
+        let changes = decoder.finish();
+
+        let row = |id: i64, name: Value| -> Row {
+            [
+                ("created_at".to_string(), json!("2024-01-02 03:04:05")),
+                ("id".to_string(), json!(id)),
+                ("name".to_string(), name),
+            ]
+            .into_iter()
+            .collect()
+        };
+        let kinds: Vec<_> = changes.iter().map(|c| (c.sequence, c.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, ChangeKind::Added),
+                (2, ChangeKind::Added),
+                (3, ChangeKind::Modified)
+            ]
+        );
+        assert_eq!(changes[0].table_name, "users");
+        assert_eq!(changes[0].after, Some(row(1, json!("John"))));
+        assert_eq!(changes[1].after, Some(row(-2, json!("Jane"))));
+        assert_eq!(changes[2].before, Some(row(1, json!("John"))));
+        assert_eq!(changes[2].after, Some(row(1, Value::Null)));
+    }
+
+    #[test]
+    fn test_decode_integers_by_signedness() {
+        let signed = ColumnInfo::new("n".to_string(), "tinyint", "tinyint(4)");
+        let unsigned = ColumnInfo::new("n".to_string(), "bigint", "bigint unsigned");
+
+        let value = decode_value(&mut Reader::new(&[0xFF]), TYPE_TINY, 0, &signed).unwrap();
+        assert_eq!(value, json!(-1));
+        let value =
+            decode_value(&mut Reader::new(&[0xFF; 8]), TYPE_LONGLONG, 0, &unsigned).unwrap();
+        assert_eq!(value, json!(u64::MAX));
+    }
+
+    #[test]
+    fn test_decode_enum_and_set() {
+        let status = ColumnInfo::new("status".to_string(), "enum", "enum('new','it''s done')");
+        assert_eq!(status.labels, vec!["new", "it's done"]);
+        let tags = ColumnInfo::new("tags".to_string(), "set", "set('a','b','c')");
+
+        let meta = ((TYPE_ENUM as u16) << 8) | 1;
+        let value = decode_value(&mut Reader::new(&[2]), TYPE_STRING, meta, &status).unwrap();
+        assert_eq!(value, json!("it's done"));
+        let meta = ((TYPE_SET as u16) << 8) | 1;
+        let value = decode_value(&mut Reader::new(&[0b101]), TYPE_STRING, meta, &tags).unwrap();
+        assert_eq!(value, json!("a,c"));
+    }
+
+    #[test]
+    fn test_decode_decimal() {
+        let data = [0x81, 0x0D, 0xFB, 0x38, 0xD2, 0x04, 0xD2];
+        let decimal = decode_decimal(&mut Reader::new(&data), 14, 4).unwrap();
+        assert_eq!(decimal, "1234567890.1234");
+
+        let negative: Vec<u8> = data.iter().map(|byte| !byte).collect();
+        let decimal = decode_decimal(&mut Reader::new(&negative), 14, 4).unwrap();
+        assert_eq!(decimal, "-1234567890.1234");
+    }
+
+    #[test]
+    fn test_decode_time2() {
+        // -01:02:03
+        let time = (1 << 12) | (2 << 6) | 3;
+        let packed = (0x80_0000 - time) as u32;
+        let value = decode_time2(&mut Reader::new(&packed.to_be_bytes()[1..]), 0).unwrap();
+        assert_eq!(value, "-01:02:03");
+
+        // 12:34:56.789
+        let time = (12 << 12) | (34 << 6) | 56;
+        let mut data = ((0x80_0000 + time) as u32).to_be_bytes()[1..].to_vec();
+        data.extend_from_slice(&7890u16.to_be_bytes());
+        let value = decode_time2(&mut Reader::new(&data), 3).unwrap();
+        assert_eq!(value, "12:34:56.789");
+    }
+
+    #[test]
+    fn test_decode_fractional_seconds_by_precision() {
+        let column = ColumnInfo::new("created_at".to_string(), "datetime", "datetime(3)");
+        assert_eq!(column.precision, 3);
+        assert_eq!(fractional_seconds(120_000, 2), ".12");
+        assert_eq!(fractional_seconds(5, 6), ".000005");
+        assert_eq!(fractional_seconds(120_000, 0), "");
+
+        // 2024-01-02 03:04:05.123
+        let mut data = image(1, None)[5..].to_vec();
+        data.extend_from_slice(&1230u16.to_be_bytes());
+        let value = decode_value(&mut Reader::new(&data), TYPE_DATETIME2, 3, &column).unwrap();
+        assert_eq!(value, json!("2024-01-02 03:04:05.123"));
+    }
+
+    #[test]
+    fn test_decode_timestamp_in_timezone() {
+        let column = ColumnInfo::new("updated_at".to_string(), "timestamp", "timestamp(1)")
+            .with_timezone("+09:00".parse().unwrap());
+        // 2024-01-02 03:04:05.5 UTC
+        let mut data = 1_704_164_645u32.to_be_bytes().to_vec();
+        data.push(50);
+        let value = decode_value(&mut Reader::new(&data), TYPE_TIMESTAMP2, 1, &column).unwrap();
+        assert_eq!(value, json!("2024-01-02 12:04:05.5"));
+    }
+
+    #[test]
+    fn test_decode_json() {
+        // {"a": 1, "b": "x"} as a small object
+        let data = [
+            0x00, 2, 0, 22, 0, // type, count, size
+            18, 0, 1, 0, 19, 0, 1, 0, // keys
+            0x05, 1, 0, 0x0C, 20, 0, // values: inline int16, string at offset 20
+            b'a', b'b', 1, b'x',
+        ];
+        assert_eq!(decode_json(&data).unwrap(), json!({"a": 1, "b": "x"}));
+        assert_eq!(decode_json(&[]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_check_plain_connection_refuses_tls() {
+        let check = |url: &str| check_plain_connection(&DbInfo::from_url(url.to_string()).unwrap());
+        assert!(check("mysql://u:p@localhost/app").is_ok());
+        assert!(check("mysql://u:p@localhost/app?ssl-mode=DISABLED").is_ok());
+        assert!(check("mysql://u:p@localhost/app?ssl-mode=preferred").is_ok());
+        assert!(check("mysql://u:p@localhost/app?ssl-mode=REQUIRED").is_err());
+        assert!(check("mysql://u:p@localhost/app?sslmode=verify-identity").is_err());
+    }
+}
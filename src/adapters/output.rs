@@ -1,4 +1,5 @@
 use crate::error::Result;
+use crate::models::{RowChange, SchemaChange, TableStatus, Timezone};
 
 /// Trait for output adapters that generate diff reports
 pub trait OutputAdapter {
@@ -8,18 +9,70 @@ pub trait OutputAdapter {
     /// End the output (write footer)
     fn end_output(&mut self) -> Result<()>;
 
+    /// Note the time zone TIMESTAMP values are shown in, before the first section
+    fn write_timezone(&mut self, timezone: Timezone) -> Result<()>;
+
     /// Write a table title/section header
     fn write_title(&mut self, title: &str) -> Result<()>;
 
-    /// Write a diff section with left (before) and right (after) content
-    fn write_diff_section(&mut self, left: &str, right: &str) -> Result<()>;
+    /// Label the table of the current section as created or dropped
+    fn write_table_status(&mut self, status: TableStatus) -> Result<()>;
+
+    /// Write a short informational note within the current section
+    fn write_note(&mut self, note: &str) -> Result<()>;
+
+    /// Write a changed row within the current section
+    fn write_row_change(&mut self, change: &RowChange) -> Result<()>;
+
+    /// Write the changes made to table definitions, as a section of their own
+    fn write_schema_changes(&mut self, changes: &[SchemaChange]) -> Result<()>;
 
     /// Write a message when no differences are detected
     fn write_no_diff_message(&mut self) -> Result<()>;
 
     /// Close the current section
     fn close_section(&mut self) -> Result<()>;
+}
+
+/// Allows choosing the output adapter at runtime (e.g. `Box<dyn OutputAdapter>`)
+impl<T: OutputAdapter + ?Sized> OutputAdapter for Box<T> {
+    fn start_output(&mut self) -> Result<()> {
+        (**self).start_output()
+    }
+
+    fn end_output(&mut self) -> Result<()> {
+        (**self).end_output()
+    }
+
+    fn write_timezone(&mut self, timezone: Timezone) -> Result<()> {
+        (**self).write_timezone(timezone)
+    }
+
+    fn write_title(&mut self, title: &str) -> Result<()> {
+        (**self).write_title(title)
+    }
+
+    fn write_table_status(&mut self, status: TableStatus) -> Result<()> {
+        (**self).write_table_status(status)
+    }
+
+    fn write_note(&mut self, note: &str) -> Result<()> {
+        (**self).write_note(note)
+    }
+
+    fn write_row_change(&mut self, change: &RowChange) -> Result<()> {
+        (**self).write_row_change(change)
+    }
+
+    fn write_schema_changes(&mut self, changes: &[SchemaChange]) -> Result<()> {
+        (**self).write_schema_changes(changes)
+    }
+
+    fn write_no_diff_message(&mut self) -> Result<()> {
+        (**self).write_no_diff_message()
+    }
 
-    /// Generate diff HTML from left and right content
-    fn generate_diff(&self, left: &str, right: &str) -> (String, String);
+    fn close_section(&mut self) -> Result<()> {
+        (**self).close_section()
+    }
 }
@@ -0,0 +1,676 @@
+use crate::adapters::database::{
+    changelog_event, decode_hex, select_table_keys, AdapterConnection, KeyColumn, RowSink,
+    SnapshotConnections, CAPTURE_TRIGGER_PREFIX, CHANGELOG_TABLE, DEFAULT_MAX_CONNECTIONS,
+};
+use crate::adapters::DatabaseAdapter;
+use crate::error::{DbbaError, Result};
+use crate::models::{
+    BinaryFormat, BinaryFormats, CaptureMode, ChangeEvent, ColumnDefinition, DbInfo, TableChecksum,
+    TableSchema, Timezone,
+};
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use serde_json::Value;
+use sqlx::postgres::{PgPool, PgPoolOptions, Postgres};
+use sqlx::{Executor, Row};
+use std::collections::{BTreeMap, HashMap};
+
+/// Schema whose tables are listed without a schema prefix
+const DEFAULT_SCHEMA: &str = "public";
+
+/// PostgreSQL database adapter
+///
+/// Rows are converted with `to_jsonb`, so Postgres itself maps its types into JSON:
+/// jsonb/json stay structured, arrays become JSON arrays, numeric becomes a JSON number,
+/// and uuid, timestamptz and enum values keep their canonical text form. bytea values are
+/// rendered in their binary format as in the other adapters.
+pub struct PostgresAdapter {
+    db_info: DbInfo,
+    schemas: Vec<String>,
+    max_connections: u32,
+    binary_formats: BinaryFormats,
+    timezone: Timezone,
+    pool: Option<PgPool>,
+    snapshot: SnapshotConnections<Postgres>,
+}
+
+impl PostgresAdapter {
+    pub fn new(db_info: DbInfo) -> Self {
+        Self {
+            db_info,
+            schemas: vec![DEFAULT_SCHEMA.to_string()],
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            binary_formats: BinaryFormats::default(),
+            timezone: Timezone::default(),
+            pool: None,
+            snapshot: SnapshotConnections::default(),
+        }
+    }
+
+    /// Set the schemas whose tables are read (default: public)
+    pub fn with_schemas(mut self, schemas: Vec<String>) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Set the maximum number of connections used to read tables concurrently
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Set how the values of bytea columns are shown
+    pub fn with_binary_formats(mut self, binary_formats: BinaryFormats) -> Self {
+        self.binary_formats = binary_formats;
+        self
+    }
+
+    /// Set the time zone timestamptz values are shown in (default: UTC)
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// `TimeZone` setting for a time zone: the name of a named zone, or a fixed offset in
+    /// POSIX form, whose offsets are west of UTC (e.g. `UTC-09:00` for +09:00)
+    fn timezone_setting(timezone: Timezone) -> String {
+        let Some(seconds) = timezone.fixed_offset() else {
+            return timezone.to_string();
+        };
+        if seconds == 0 {
+            return "UTC".to_string();
+        }
+        let sign = if seconds > 0 { '-' } else { '+' };
+        let minutes = seconds.abs() / 60;
+        format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+
+    /// Get a connection to run queries on, inside the consistent snapshot if one is in
+    /// progress
+    async fn connection(&self) -> Result<AdapterConnection<Postgres>> {
+        self.snapshot.acquire(self.pool()?).await
+    }
+
+    /// Get the connection pool
+    fn pool(&self) -> Result<&PgPool> {
+        self.pool
+            .as_ref()
+            .ok_or_else(|| DbbaError::Config("Database not connected".to_string()))
+    }
+
+    /// Name used for a table in snapshots and reports
+    fn table_name(schema: &str, table: &str) -> String {
+        if schema == DEFAULT_SCHEMA {
+            table.to_string()
+        } else {
+            format!("{}.{}", schema, table)
+        }
+    }
+
+    /// Split a table name used in snapshots back into (schema, table)
+    fn split_table_name(&self, table_name: &str) -> (String, String) {
+        match table_name.split_once('.') {
+            Some((schema, table)) if self.schemas.iter().any(|s| s == schema) => {
+                (schema.to_string(), table.to_string())
+            }
+            _ => (DEFAULT_SCHEMA.to_string(), table_name.to_string()),
+        }
+    }
+
+    /// Quote an identifier for use in SQL
+    fn quote_ident(ident: &str) -> String {
+        format!("\"{}\"", ident.replace('"', "\"\""))
+    }
+
+    /// Quote a string literal for use in SQL
+    fn quote_literal(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// List (schema, table) pairs of all base tables in the configured schemas
+    async fn schema_tables(&self) -> Result<Vec<(String, String)>> {
+        let mut connection = self.connection().await?;
+        let query = r#"
+            SELECT table_schema::text, table_name::text
+            FROM information_schema.tables
+            WHERE table_schema = ANY($1) AND table_type = 'BASE TABLE'
+            ORDER BY table_schema, table_name
+        "#;
+
+        let rows = sqlx::query(query)
+            .bind(&self.schemas)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut tables = Vec::new();
+        for row in rows {
+            tables.push((row.try_get(0)?, row.try_get(1)?));
+        }
+
+        Ok(tables)
+    }
+
+    /// Discover the identifying key columns of a table from its unique indexes
+    async fn index_key_columns(&self, schema: &str, table: &str) -> Result<Vec<String>> {
+        let mut connection = self.connection().await?;
+        let query = r#"
+            SELECT n.nspname::text, c.relname::text, ic.relname::text, i.indisprimary,
+                   a.attname::text, NOT a.attnotnull
+            FROM pg_index i
+            JOIN pg_class c ON c.oid = i.indrelid
+            JOIN pg_class ic ON ic.oid = i.indexrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            CROSS JOIN LATERAL unnest(i.indkey) WITH ORDINALITY AS k(attnum, ord)
+            JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = k.attnum
+            WHERE n.nspname = $1
+              AND c.relname = $2
+              AND i.indisunique
+              AND i.indpred IS NULL
+              AND i.indexprs IS NULL
+            ORDER BY n.nspname, c.relname, ic.relname, k.ord
+        "#;
+
+        let rows = sqlx::query(query)
+            .bind(schema)
+            .bind(table)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut key_columns = Vec::new();
+        for row in rows {
+            key_columns.push(KeyColumn {
+                table_name: row.try_get(1)?,
+                constraint_name: row.try_get(2)?,
+                is_primary: row.try_get(3)?,
+                column_name: row.try_get(4)?,
+                is_nullable: row.try_get(5)?,
+            });
+        }
+
+        Ok(select_table_keys(&key_columns)
+            .remove(table)
+            .unwrap_or_default())
+    }
+
+    /// The bytea columns of a table, with the format their values are shown in
+    async fn binary_columns(
+        &self,
+        schema: &str,
+        table: &str,
+    ) -> Result<HashMap<String, BinaryFormat>> {
+        let mut connection = self.connection().await?;
+        let query = r#"
+            SELECT column_name::text
+            FROM information_schema.columns
+            WHERE table_schema = $1 AND table_name = $2 AND data_type = 'bytea'
+        "#;
+
+        let rows = sqlx::query(query)
+            .bind(schema)
+            .bind(table)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let table_name = Self::table_name(schema, table);
+        let mut columns = HashMap::new();
+        for row in rows {
+            let column: String = row.try_get(0)?;
+            let format = self.binary_formats.format(&table_name, &column);
+            columns.insert(column, format);
+        }
+
+        Ok(columns)
+    }
+
+    /// Schema holding the changelog table and trigger function of `--mode triggers`
+    fn capture_schema(&self) -> &str {
+        self.schemas
+            .first()
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_SCHEMA)
+    }
+
+    /// Remove the changelog table and the trigger function, along with the triggers
+    /// using it, including ones left behind by an earlier run
+    async fn drop_capture(&self) -> Result<()> {
+        let schema = Self::quote_ident(self.capture_schema());
+        let statement = format!(
+            "DROP FUNCTION IF EXISTS {}.{}() CASCADE; DROP TABLE IF EXISTS {}.{}",
+            schema, CAPTURE_TRIGGER_PREFIX, schema, CHANGELOG_TABLE
+        );
+        let mut connection = self.pool()?.acquire().await?;
+        (&mut *connection).execute(statement.as_str()).await?;
+        Ok(())
+    }
+
+    /// Parse a row recorded by the capture trigger
+    fn changelog_row(
+        json: Option<String>,
+        binary_columns: &HashMap<String, BinaryFormat>,
+    ) -> Result<Option<BTreeMap<String, Value>>> {
+        json.map(|json| Self::row_to_map(&json, binary_columns))
+            .transpose()
+    }
+
+    /// Convert a `to_jsonb` row object to a BTreeMap (sorted keys)
+    fn row_to_map(
+        json: &str,
+        binary_columns: &HashMap<String, BinaryFormat>,
+    ) -> Result<BTreeMap<String, Value>> {
+        let Value::Object(object) = serde_json::from_str(json)? else {
+            return Err(DbbaError::Unknown(format!(
+                "Expected a JSON object for row, got: {}",
+                json
+            )));
+        };
+
+        Ok(object
+            .into_iter()
+            .map(|(column, value)| {
+                let value = match binary_columns.get(&column) {
+                    Some(format) => Self::render_bytea(value, *format),
+                    None => value,
+                };
+                (column, value)
+            })
+            .collect())
+    }
+
+    /// Render a bytea value (`"\\x0123..."` in JSON) in the given format
+    fn render_bytea(value: Value, format: BinaryFormat) -> Value {
+        let Value::String(text) = &value else {
+            return value;
+        };
+        let Some(hex) = text.strip_prefix("\\x") else {
+            return value;
+        };
+
+        match decode_hex(hex) {
+            Some(bytes) => format.render(&bytes),
+            None => value,
+        }
+    }
+}
+
+#[async_trait]
+impl DatabaseAdapter for PostgresAdapter {
+    async fn connect(&mut self) -> Result<()> {
+        let mut options = PgPoolOptions::new().max_connections(self.max_connections);
+        // Connections start in UTC
+        if self.timezone != Timezone::utc() {
+            let statement = format!("SET TIME ZONE '{}'", Self::timezone_setting(self.timezone));
+            options = options.after_connect(move |connection, _| {
+                let statement = statement.clone();
+                Box::pin(async move {
+                    connection.execute(statement.as_str()).await?;
+                    Ok(())
+                })
+            });
+        }
+        let pool = options.connect(&self.db_info.connection_url()).await?;
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(pool) = self.pool.take() {
+            pool.close().await;
+        }
+        Ok(())
+    }
+
+    async fn key_columns(&self, table_name: &str) -> Result<Vec<String>> {
+        let (schema, table) = self.split_table_name(table_name);
+        self.index_key_columns(&schema, &table).await
+    }
+
+    async fn scan_table(&self, table_name: &str, sink: &mut RowSink<'_>) -> Result<()> {
+        let (schema, table) = self.split_table_name(table_name);
+        let binary_columns = self.binary_columns(&schema, &table).await?;
+
+        // Let Postgres convert each row into a JSON object
+        let query = format!(
+            "SELECT to_jsonb(t)::text FROM {}.{} t",
+            Self::quote_ident(&schema),
+            Self::quote_ident(&table)
+        );
+        let mut connection = self.connection().await?;
+        let mut rows = sqlx::query(&query).fetch(&mut *connection);
+        while let Some(row) = rows.try_next().await? {
+            let json: String = row.try_get(0)?;
+            sink(Self::row_to_map(&json, &binary_columns)?)?;
+        }
+
+        Ok(())
+    }
+
+    async fn table_checksum(&self, table_name: &str) -> Result<Option<TableChecksum>> {
+        let mut connection = self.connection().await?;
+        let (schema, table) = self.split_table_name(table_name);
+
+        // Sum a 64-bit hash of every row; unlike XOR, duplicate rows don't cancel out
+        let query = format!(
+            "SELECT count(*), coalesce(sum(('x' || substr(md5(t::text), 1, 16))::bit(64)::bigint::numeric), 0)::text FROM {}.{} t",
+            Self::quote_ident(&schema),
+            Self::quote_ident(&table)
+        );
+        let (row_count, checksum): (i64, String) =
+            sqlx::query_as(&query).fetch_one(&mut *connection).await?;
+
+        Ok(Some(TableChecksum {
+            row_count: Some(row_count),
+            checksum,
+        }))
+    }
+
+    async fn table_schema(&self, table_name: &str) -> Result<Option<TableSchema>> {
+        let mut connection = self.connection().await?;
+        let (schema_name, table) = self.split_table_name(table_name);
+        let relation = format!(
+            "{}.{}",
+            Self::quote_ident(&schema_name),
+            Self::quote_ident(&table)
+        );
+        let mut schema = TableSchema::default();
+
+        let query = r#"
+            SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull,
+                   pg_get_expr(d.adbin, d.adrelid)
+            FROM pg_attribute a
+            LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+            WHERE a.attrelid = $1::regclass AND a.attnum > 0 AND NOT a.attisdropped
+            ORDER BY a.attnum
+        "#;
+        let rows = sqlx::query(query)
+            .bind(&relation)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            schema.columns.push(ColumnDefinition {
+                name: row.try_get(0)?,
+                data_type: row.try_get(1)?,
+                nullable: row.try_get(2)?,
+                default: row.try_get(3)?,
+            });
+        }
+
+        // pg_get_indexdef gives the column name, or the expression of expression indexes
+        let query = r#"
+            SELECT ic.relname::text, i.indisprimary, i.indisunique,
+                   pg_get_indexdef(i.indexrelid, k.ord::int, true)
+            FROM pg_index i
+            JOIN pg_class ic ON ic.oid = i.indexrelid
+            CROSS JOIN LATERAL unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord)
+            WHERE i.indrelid = $1::regclass
+            ORDER BY ic.relname, k.ord
+        "#;
+        let rows = sqlx::query(query)
+            .bind(&relation)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let name: String = row.try_get(0)?;
+            schema.push_index_column(&name, row.try_get(1)?, row.try_get(2)?, row.try_get(3)?);
+        }
+
+        let query = r#"
+            SELECT con.conname::text, a.attname::text, rn.nspname::text, rc.relname::text,
+                   ra.attname::text
+            FROM pg_constraint con
+            CROSS JOIN LATERAL unnest(con.conkey, con.confkey) WITH ORDINALITY AS k(attnum, refnum, ord)
+            JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+            JOIN pg_class rc ON rc.oid = con.confrelid
+            JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+            JOIN pg_attribute ra ON ra.attrelid = con.confrelid AND ra.attnum = k.refnum
+            WHERE con.conrelid = $1::regclass AND con.contype = 'f'
+            ORDER BY con.conname, k.ord
+        "#;
+        let rows = sqlx::query(query)
+            .bind(&relation)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let name: String = row.try_get(0)?;
+            let referenced_schema: String = row.try_get(2)?;
+            let referenced_table: String = row.try_get(3)?;
+            schema.push_foreign_key_column(
+                &name,
+                row.try_get(1)?,
+                Self::table_name(&referenced_schema, &referenced_table),
+                row.try_get(4)?,
+            );
+        }
+
+        Ok(Some(schema))
+    }
+
+    async fn begin_snapshot(&self, connections: usize) -> Result<usize> {
+        let pool = self.pool()?;
+        let connections = connections.clamp(1, pool.options().get_max_connections() as usize);
+
+        // Export the first transaction's snapshot and import it into the others
+        let mut first = pool.acquire().await?;
+        (&mut *first)
+            .execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await?;
+        let snapshot_id: String = sqlx::query_scalar("SELECT pg_export_snapshot()")
+            .fetch_one(&mut *first)
+            .await?;
+
+        let import = format!(
+            "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; SET TRANSACTION SNAPSHOT {}",
+            Self::quote_literal(&snapshot_id)
+        );
+        let mut snapshot_connections = vec![first];
+        for _ in 1..connections {
+            let mut connection = pool.acquire().await?;
+            (&mut *connection).execute(import.as_str()).await?;
+            snapshot_connections.push(connection);
+        }
+
+        self.snapshot.start(snapshot_connections);
+        Ok(connections)
+    }
+
+    async fn end_snapshot(&self) -> Result<()> {
+        for mut connection in self.snapshot.finish()? {
+            (&mut *connection).execute("ROLLBACK").await?;
+        }
+        Ok(())
+    }
+
+    async fn start_capture(&self, mode: CaptureMode, table_names: &[String]) -> Result<()> {
+        if mode != CaptureMode::Triggers {
+            return Err(DbbaError::Config(format!(
+                "--mode {} is not supported by PostgreSQL",
+                mode
+            )));
+        }
+
+        self.drop_capture().await?;
+
+        // One function records the changes of every table, named by the trigger. Rows are
+        // converted in the chosen time zone rather than that of the use case's session.
+        let schema = Self::quote_ident(self.capture_schema());
+        let settings = format!("SET TimeZone = '{}'", Self::timezone_setting(self.timezone));
+        let statement = format!(
+            r#"
+            CREATE TABLE {schema}.{changelog} (
+                id bigserial PRIMARY KEY,
+                table_schema text NOT NULL,
+                table_name text NOT NULL,
+                operation text NOT NULL,
+                old_row jsonb,
+                new_row jsonb
+            );
+            CREATE FUNCTION {schema}.{function}() RETURNS trigger LANGUAGE plpgsql {settings} AS $$
+            BEGIN
+                IF TG_OP = 'INSERT' THEN
+                    INSERT INTO {schema}.{changelog} (table_schema, table_name, operation, new_row)
+                    VALUES (TG_TABLE_SCHEMA, TG_TABLE_NAME, TG_OP, to_jsonb(NEW));
+                ELSIF TG_OP = 'UPDATE' THEN
+                    INSERT INTO {schema}.{changelog} (table_schema, table_name, operation, old_row, new_row)
+                    VALUES (TG_TABLE_SCHEMA, TG_TABLE_NAME, TG_OP, to_jsonb(OLD), to_jsonb(NEW));
+                ELSE
+                    INSERT INTO {schema}.{changelog} (table_schema, table_name, operation, old_row)
+                    VALUES (TG_TABLE_SCHEMA, TG_TABLE_NAME, TG_OP, to_jsonb(OLD));
+                END IF;
+                RETURN NULL;
+            END
+            $$
+            "#,
+            schema = schema,
+            changelog = CHANGELOG_TABLE,
+            function = CAPTURE_TRIGGER_PREFIX,
+            settings = settings,
+        );
+        let mut connection = self.pool()?.acquire().await?;
+        (&mut *connection).execute(statement.as_str()).await?;
+
+        let changelog = Self::table_name(self.capture_schema(), CHANGELOG_TABLE);
+        for table_name in table_names.iter().filter(|name| **name != changelog) {
+            let (table_schema, table) = self.split_table_name(table_name);
+            let statement = format!(
+                "CREATE TRIGGER {} AFTER INSERT OR UPDATE OR DELETE ON {}.{} \
+                 FOR EACH ROW EXECUTE FUNCTION {}.{}()",
+                CAPTURE_TRIGGER_PREFIX,
+                Self::quote_ident(&table_schema),
+                Self::quote_ident(&table),
+                schema,
+                CAPTURE_TRIGGER_PREFIX
+            );
+            (&mut *connection).execute(statement.as_str()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn finish_capture(&self) -> Result<Vec<ChangeEvent>> {
+        let query = format!(
+            "SELECT id, table_schema, table_name, operation, old_row::text, new_row::text \
+             FROM {}.{} ORDER BY id",
+            Self::quote_ident(self.capture_schema()),
+            CHANGELOG_TABLE
+        );
+        let rows = sqlx::query(&query).fetch_all(self.pool()?).await;
+        // Remove the triggers even if the changes can't be read
+        self.drop_capture().await?;
+
+        let mut binary_columns = HashMap::new();
+        let mut events = Vec::new();
+        for row in rows? {
+            let schema: String = row.try_get(1)?;
+            let table: String = row.try_get(2)?;
+            let table_name = Self::table_name(&schema, &table);
+            if !binary_columns.contains_key(&table_name) {
+                let columns = self.binary_columns(&schema, &table).await?;
+                binary_columns.insert(table_name.clone(), columns);
+            }
+            let columns = &binary_columns[&table_name];
+
+            events.push(changelog_event(
+                row.try_get::<i64, _>(0)? as u64,
+                table_name.clone(),
+                row.try_get(3)?,
+                Self::changelog_row(row.try_get(4)?, columns)?,
+                Self::changelog_row(row.try_get(5)?, columns)?,
+            )?);
+        }
+
+        Ok(events)
+    }
+
+    async fn abort_capture(&self) -> Result<()> {
+        self.drop_capture().await
+    }
+
+    async fn list_tables(&self) -> Result<Vec<String>> {
+        Ok(self
+            .schema_tables()
+            .await?
+            .iter()
+            .map(|(schema, table)| Self::table_name(schema, table))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_table_name_qualifies_non_default_schema() {
+        assert_eq!(PostgresAdapter::table_name("public", "users"), "users");
+        assert_eq!(
+            PostgresAdapter::table_name("billing", "invoices"),
+            "billing.invoices"
+        );
+    }
+
+    #[test]
+    fn test_split_table_name() {
+        let adapter =
+            PostgresAdapter::new(DbInfo::from_url("postgres://localhost/app".to_string()).unwrap())
+                .with_schemas(vec!["public".to_string(), "billing".to_string()]);
+
+        assert_eq!(
+            adapter.split_table_name("billing.invoices"),
+            ("billing".to_string(), "invoices".to_string())
+        );
+        assert_eq!(
+            adapter.split_table_name("users"),
+            ("public".to_string(), "users".to_string())
+        );
+        assert_eq!(
+            adapter.split_table_name("odd.name"),
+            ("public".to_string(), "odd.name".to_string())
+        );
+    }
+
+    #[test]
+    fn test_timezone_setting_inverts_offset() {
+        let setting = |tz: &str| PostgresAdapter::timezone_setting(tz.parse().unwrap());
+        assert_eq!(setting("UTC"), "UTC");
+        assert_eq!(setting("+09:00"), "UTC-09:00");
+        assert_eq!(setting("-03:30"), "UTC+03:30");
+        assert_eq!(setting("Asia/Tokyo"), "Asia/Tokyo");
+    }
+
+    #[test]
+    fn test_quote_ident_escapes_quotes() {
+        assert_eq!(PostgresAdapter::quote_ident("odd\"name"), "\"odd\"\"name\"");
+    }
+
+    #[test]
+    fn test_row_to_map_keeps_structured_values() {
+        let json = r#"{"id":"6f1c1d56-0a52-4b8e-9d1c-1f2a3b4c5d6e","tags":["a","b"],"settings":{"theme":"dark"},"status":"shipped"}"#;
+
+        let row = PostgresAdapter::row_to_map(json, &HashMap::new()).unwrap();
+
+        assert_eq!(row["id"], json!("6f1c1d56-0a52-4b8e-9d1c-1f2a3b4c5d6e"));
+        assert_eq!(row["tags"], json!(["a", "b"]));
+        assert_eq!(row["settings"], json!({"theme": "dark"}));
+        assert_eq!(row["status"], json!("shipped"));
+    }
+
+    #[test]
+    fn test_row_to_map_renders_bytea() {
+        let json = r#"{"id":1,"payload":"\\x68656c6c6f","raw":"\\x0102"}"#;
+        let binary_columns: HashMap<String, BinaryFormat> = [
+            ("payload".to_string(), BinaryFormat::Md5),
+            ("raw".to_string(), BinaryFormat::Hex),
+        ]
+        .into_iter()
+        .collect();
+
+        let row = PostgresAdapter::row_to_map(json, &binary_columns).unwrap();
+
+        assert_eq!(
+            row["payload"],
+            json!("MD5 Digest value: 5d41402abc4b2a76b9719d911017c592")
+        );
+        assert_eq!(row["raw"], json!("0x0102"));
+        assert_eq!(row["id"], json!(1));
+    }
+}
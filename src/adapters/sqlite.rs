@@ -0,0 +1,746 @@
+use crate::adapters::database::{
+    changelog_event, decode_hex, AdapterConnection, RowSink, SnapshotConnections,
+    CAPTURE_TRIGGER_PREFIX, CHANGELOG_TABLE, DEFAULT_MAX_CONNECTIONS,
+};
+use crate::adapters::DatabaseAdapter;
+use crate::error::{DbbaError, Result};
+use crate::models::{
+    BinaryFormats, CaptureMode, ChangeEvent, ColumnDefinition, DbInfo, TableSchema,
+};
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use serde_json::Value;
+use sqlx::sqlite::{
+    Sqlite, SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions, SqliteRow,
+};
+use sqlx::{Column, ConnectOptions, Executor, Row, TypeInfo, ValueRef};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Column used to identify rows of tables without a declared primary key
+const ROWID_COLUMN: &str = "rowid";
+
+/// Name given to primary keys in table definitions, which SQLite doesn't name
+const PRIMARY_KEY_NAME: &str = "PRIMARY";
+
+/// SQLite database adapter
+pub struct SqliteAdapter {
+    db_info: DbInfo,
+    max_connections: u32,
+    binary_formats: BinaryFormats,
+    pool: Option<SqlitePool>,
+    snapshot: SnapshotConnections<Sqlite>,
+}
+
+impl SqliteAdapter {
+    pub fn new(db_info: DbInfo) -> Self {
+        Self {
+            db_info,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            binary_formats: BinaryFormats::default(),
+            pool: None,
+            snapshot: SnapshotConnections::default(),
+        }
+    }
+
+    /// Set the maximum number of connections used to read tables concurrently
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Set how the values of blobs are shown
+    pub fn with_binary_formats(mut self, binary_formats: BinaryFormats) -> Self {
+        self.binary_formats = binary_formats;
+        self
+    }
+
+    /// Get a connection to run queries on, inside the consistent snapshot if one is in
+    /// progress
+    async fn connection(&self) -> Result<AdapterConnection<Sqlite>> {
+        self.snapshot.acquire(self.pool()?).await
+    }
+
+    /// Get the connection pool
+    fn pool(&self) -> Result<&SqlitePool> {
+        self.pool
+            .as_ref()
+            .ok_or_else(|| DbbaError::Config("Database not connected".to_string()))
+    }
+
+    /// Quote an identifier for use in SQL
+    fn quote_ident(ident: &str) -> String {
+        format!("\"{}\"", ident.replace('"', "\"\""))
+    }
+
+    /// Quote a string literal for use in SQL
+    fn quote_literal(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// Open a connection that can write, for installing and removing the capture
+    /// triggers (the pool only reads)
+    async fn writer(&self) -> Result<SqliteConnection> {
+        let options =
+            SqliteConnectOptions::from_str(&self.db_info.connection_url())?.read_only(false);
+        Ok(options.connect().await?)
+    }
+
+    /// Remove the capture triggers and the changelog table, including ones left behind
+    /// by an earlier run
+    async fn drop_capture(connection: &mut SqliteConnection) -> Result<()> {
+        let query = format!(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND name LIKE '{}\\_%' ESCAPE '\\'",
+            CAPTURE_TRIGGER_PREFIX
+        );
+        let rows = sqlx::query(&query).fetch_all(&mut *connection).await?;
+        for row in rows {
+            let trigger: String = row.try_get(0)?;
+            let statement = format!("DROP TRIGGER IF EXISTS {}", Self::quote_ident(&trigger));
+            (&mut *connection).execute(statement.as_str()).await?;
+        }
+
+        let statement = format!("DROP TABLE IF EXISTS {}", CHANGELOG_TABLE);
+        (&mut *connection).execute(statement.as_str()).await?;
+        Ok(())
+    }
+
+    /// `json_object` arguments recording the columns of the `OLD` or `NEW` row.
+    ///
+    /// Blobs can't be held in JSON, so they are recorded as a one-element array of
+    /// their hex digits, which no other value produces.
+    async fn row_json(&self, table_name: &str, row: &str) -> Result<String> {
+        let mut connection = self.connection().await?;
+        let rows = sqlx::query("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut arguments = Vec::new();
+        if self.primary_key(table_name).await?.is_empty() {
+            arguments.push(format!("'{}', {}.rowid", ROWID_COLUMN, row));
+        }
+        for row_data in rows {
+            let column: String = row_data.try_get(0)?;
+            let value = format!("{}.{}", row, Self::quote_ident(&column));
+            arguments.push(format!(
+                "{}, CASE typeof({}) WHEN 'blob' THEN json_array(hex({})) ELSE {} END",
+                Self::quote_literal(&column),
+                value,
+                value,
+                value
+            ));
+        }
+
+        Ok(format!("json_object({})", arguments.join(", ")))
+    }
+
+    /// Parse a row recorded by the capture triggers, rendering blobs in their binary
+    /// format
+    fn changelog_row(
+        &self,
+        table_name: &str,
+        json: Option<String>,
+    ) -> Result<Option<BTreeMap<String, Value>>> {
+        let Some(json) = json else {
+            return Ok(None);
+        };
+        let mut row: BTreeMap<String, Value> = serde_json::from_str(&json)?;
+        for (column, value) in row.iter_mut() {
+            if let Value::Array(items) = value {
+                let bytes = match items.as_slice() {
+                    [Value::String(hex)] => decode_hex(hex),
+                    _ => None,
+                };
+                if let Some(bytes) = bytes {
+                    *value = self
+                        .binary_formats
+                        .format(table_name, column)
+                        .render(&bytes);
+                }
+            }
+        }
+        Ok(Some(row))
+    }
+
+    /// Declared primary key columns of a table, in key order
+    async fn primary_key(&self, table_name: &str) -> Result<Vec<String>> {
+        let mut connection = self.connection().await?;
+        let query = "SELECT name FROM pragma_table_info(?) WHERE pk > 0 ORDER BY pk";
+
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+
+        let mut columns = Vec::new();
+        for row in rows {
+            columns.push(row.try_get(0)?);
+        }
+
+        Ok(columns)
+    }
+
+    /// Convert a SQLite row to a BTreeMap (sorted keys)
+    ///
+    /// SQLite is dynamically typed, so values are mapped by the storage class of each
+    /// value rather than by the declared column type.
+    fn row_to_hashmap(&self, table_name: &str, row: &SqliteRow) -> Result<BTreeMap<String, Value>> {
+        let mut map = BTreeMap::new();
+
+        for (i, column) in row.columns().iter().enumerate() {
+            let column_name = column.name();
+
+            let raw_value = row.try_get_raw(i)?;
+            if raw_value.is_null() {
+                map.insert(column_name.to_string(), Value::Null);
+                continue;
+            }
+            let storage_class = raw_value.type_info().name().to_string();
+
+            let value: Value = match storage_class.as_str() {
+                "INTEGER" => Value::Number(row.try_get_unchecked::<i64, _>(i)?.into()),
+                "REAL" => serde_json::Number::from_f64(row.try_get_unchecked::<f64, _>(i)?)
+                    .map(Value::Number)
+                    .unwrap_or(Value::Null),
+                "BLOB" => {
+                    let bytes = row.try_get_unchecked::<Vec<u8>, _>(i)?;
+                    self.binary_formats
+                        .format(table_name, column_name)
+                        .render(&bytes)
+                }
+                _ => Value::String(row.try_get_unchecked::<String, _>(i)?),
+            };
+
+            map.insert(column_name.to_string(), value);
+        }
+
+        Ok(map)
+    }
+}
+
+#[async_trait]
+impl DatabaseAdapter for SqliteAdapter {
+    async fn connect(&mut self) -> Result<()> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(self.max_connections)
+            .connect(&self.db_info.connection_url())
+            .await?;
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(pool) = self.pool.take() {
+            pool.close().await;
+        }
+        Ok(())
+    }
+
+    async fn key_columns(&self, table_name: &str) -> Result<Vec<String>> {
+        // Fall back to the implicit rowid when no primary key is declared
+        let key_columns = self.primary_key(table_name).await?;
+        if key_columns.is_empty() {
+            Ok(vec![ROWID_COLUMN.to_string()])
+        } else {
+            Ok(key_columns)
+        }
+    }
+
+    async fn scan_table(&self, table_name: &str, sink: &mut RowSink<'_>) -> Result<()> {
+        let query = if self.primary_key(table_name).await?.is_empty() {
+            format!(
+                "SELECT rowid AS {}, * FROM {}",
+                ROWID_COLUMN,
+                Self::quote_ident(table_name)
+            )
+        } else {
+            format!("SELECT * FROM {}", Self::quote_ident(table_name))
+        };
+        let mut connection = self.connection().await?;
+        let mut rows = sqlx::query(&query).fetch(&mut *connection);
+        while let Some(row) = rows.try_next().await? {
+            sink(self.row_to_hashmap(table_name, &row)?)?;
+        }
+
+        Ok(())
+    }
+
+    async fn table_schema(&self, table_name: &str) -> Result<Option<TableSchema>> {
+        // Before taking a connection, which may be the only one of the snapshot
+        let primary_key = self.primary_key(table_name).await?;
+        let mut connection = self.connection().await?;
+        let mut schema = TableSchema::default();
+
+        let query =
+            r#"SELECT name, type, "notnull", dflt_value FROM pragma_table_info(?) ORDER BY cid"#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            schema.columns.push(ColumnDefinition {
+                name: row.try_get(0)?,
+                data_type: row.try_get(1)?,
+                nullable: row.try_get::<i64, _>(2)? == 0,
+                default: row.try_get(3)?,
+            });
+        }
+
+        // An INTEGER PRIMARY KEY has no index of its own, so the key is taken from the
+        // columns and the index behind other primary keys is skipped
+        for column in primary_key {
+            schema.push_index_column(PRIMARY_KEY_NAME, true, true, column);
+        }
+        let query = r#"
+            SELECT il.name, il."unique", ii.name
+            FROM pragma_index_list(?) il, pragma_index_info(il.name) ii
+            WHERE il.origin <> 'pk'
+            ORDER BY il.name, ii.seqno
+        "#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        for row in rows {
+            let name: String = row.try_get(0)?;
+            let column: Option<String> = row.try_get(2)?;
+            schema.push_index_column(
+                &name,
+                false,
+                row.try_get::<i64, _>(1)? != 0,
+                column.unwrap_or_else(|| "(expression)".to_string()),
+            );
+        }
+
+        // Foreign keys have no name either, so they are named by their columns
+        let query =
+            r#"SELECT id, "table", "from", "to" FROM pragma_foreign_key_list(?) ORDER BY id, seq"#;
+        let rows = sqlx::query(query)
+            .bind(table_name)
+            .fetch_all(&mut *connection)
+            .await?;
+        let mut foreign_keys = TableSchema::default();
+        for row in rows {
+            foreign_keys.push_foreign_key_column(
+                &row.try_get::<i64, _>(0)?.to_string(),
+                row.try_get(2)?,
+                row.try_get(1)?,
+                row.try_get(3)?,
+            );
+        }
+        for mut foreign_key in foreign_keys.foreign_keys {
+            foreign_key.name = foreign_key.columns.join(", ");
+            schema.foreign_keys.push(foreign_key);
+        }
+
+        Ok(Some(schema))
+    }
+
+    async fn begin_snapshot(&self, _connections: usize) -> Result<usize> {
+        // A read transaction can't be shared, so everything is read on one connection.
+        // The snapshot is taken by the first read, not by BEGIN.
+        let mut connection = self.pool()?.acquire().await?;
+        (&mut *connection)
+            .execute("BEGIN; SELECT count(*) FROM sqlite_master")
+            .await?;
+
+        self.snapshot.start(vec![connection]);
+        Ok(1)
+    }
+
+    async fn end_snapshot(&self) -> Result<()> {
+        for mut connection in self.snapshot.finish()? {
+            (&mut *connection).execute("ROLLBACK").await?;
+        }
+        Ok(())
+    }
+
+    async fn start_capture(&self, mode: CaptureMode, table_names: &[String]) -> Result<()> {
+        if mode != CaptureMode::Triggers {
+            return Err(DbbaError::Config(format!(
+                "--mode {} is not supported by SQLite",
+                mode
+            )));
+        }
+
+        let mut connection = self.writer().await?;
+        Self::drop_capture(&mut connection).await?;
+
+        let statement = format!(
+            "CREATE TABLE {} (id INTEGER PRIMARY KEY AUTOINCREMENT, table_name TEXT NOT NULL, \
+             operation TEXT NOT NULL, old_row TEXT, new_row TEXT)",
+            CHANGELOG_TABLE
+        );
+        (&mut connection).execute(statement.as_str()).await?;
+
+        let tables = table_names
+            .iter()
+            .filter(|table_name| table_name.as_str() != CHANGELOG_TABLE);
+        for (i, table_name) in tables.enumerate() {
+            let old_row = self.row_json(table_name, "OLD").await?;
+            let new_row = self.row_json(table_name, "NEW").await?;
+            for (operation, old_row, new_row) in [
+                ("INSERT", "NULL", new_row.as_str()),
+                ("UPDATE", old_row.as_str(), new_row.as_str()),
+                ("DELETE", old_row.as_str(), "NULL"),
+            ] {
+                let statement = format!(
+                    "CREATE TRIGGER {}_{}_{} AFTER {} ON {} BEGIN \
+                     INSERT INTO {} (table_name, operation, old_row, new_row) \
+                     VALUES ({}, '{}', {}, {}); END",
+                    CAPTURE_TRIGGER_PREFIX,
+                    i,
+                    operation.to_lowercase(),
+                    operation,
+                    Self::quote_ident(table_name),
+                    CHANGELOG_TABLE,
+                    Self::quote_literal(table_name),
+                    operation,
+                    old_row,
+                    new_row
+                );
+                (&mut connection).execute(statement.as_str()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn finish_capture(&self) -> Result<Vec<ChangeEvent>> {
+        let mut connection = self.writer().await?;
+        let query = format!(
+            "SELECT id, table_name, operation, old_row, new_row FROM {} ORDER BY id",
+            CHANGELOG_TABLE
+        );
+        let rows = sqlx::query(&query).fetch_all(&mut connection).await;
+        // Remove the triggers even if the changes can't be read
+        Self::drop_capture(&mut connection).await?;
+
+        let mut events = Vec::new();
+        for row in rows? {
+            let table_name: String = row.try_get(1)?;
+            events.push(changelog_event(
+                row.try_get::<i64, _>(0)? as u64,
+                table_name.clone(),
+                row.try_get(2)?,
+                self.changelog_row(&table_name, row.try_get(3)?)?,
+                self.changelog_row(&table_name, row.try_get(4)?)?,
+            )?);
+        }
+
+        Ok(events)
+    }
+
+    async fn abort_capture(&self) -> Result<()> {
+        let mut connection = self.writer().await?;
+        Self::drop_capture(&mut connection).await
+    }
+
+    async fn list_tables(&self) -> Result<Vec<String>> {
+        let mut connection = self.connection().await?;
+        let query = "SELECT name FROM sqlite_master \
+                     WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name";
+
+        let rows = sqlx::query(query).fetch_all(&mut *connection).await?;
+
+        let mut table_names = Vec::new();
+        for row in rows {
+            table_names.push(row.try_get(0)?);
+        }
+
+        Ok(table_names)
+    }
+}
+
+/// Decode a string of hex digits, or `None` if it isn't one
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::models::{BinaryFormat, ChangeKind, TableFilter};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// A SQLite database file in the temp directory, removed when dropped
+    pub(crate) struct TestDatabase {
+        pub(crate) path: PathBuf,
+    }
+
+    impl TestDatabase {
+        /// Create a database file and run the given SQL in it
+        pub(crate) async fn create(sql: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("dbba_test_{}.db", ulid::Ulid::new()));
+            let db = Self { path };
+            db.execute(sql).await;
+            db
+        }
+
+        /// Run SQL against the database with a separate read-write connection
+        pub(crate) async fn execute(&self, sql: &str) {
+            let url = format!("sqlite://{}?mode=rwc", self.path.display());
+            let pool = SqlitePool::connect(&url).await.unwrap();
+            sqlx::raw_sql(sql).execute(&pool).await.unwrap();
+            pool.close().await;
+        }
+
+        /// Create a connected adapter for the database
+        pub(crate) async fn adapter(&self) -> SqliteAdapter {
+            let url = format!("sqlite://{}", self.path.display());
+            let mut adapter = SqliteAdapter::new(DbInfo::from_url(url).unwrap());
+            adapter.connect().await.unwrap();
+            adapter
+        }
+    }
+
+    impl Drop for TestDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+            for suffix in ["-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_database_uses_declared_primary_key() {
+        let db = TestDatabase::create(
+            "CREATE TABLE user_roles (user_id INTEGER, role_id INTEGER, PRIMARY KEY (user_id, role_id));
+             INSERT INTO user_roles VALUES (1, 1), (1, 2);",
+        )
+        .await;
+
+        let snapshot = db
+            .adapter()
+            .await
+            .read_database(&TableFilter::default())
+            .await
+            .unwrap();
+
+        let table = &snapshot["user_roles"];
+        assert_eq!(table.key_columns, vec!["user_id", "role_id"]);
+        assert_eq!(table.rows.len(), 2);
+        assert!(!table.rows[0].contains_key(ROWID_COLUMN));
+    }
+
+    #[tokio::test]
+    async fn test_read_database_falls_back_to_rowid() {
+        let db = TestDatabase::create(
+            "CREATE TABLE logs (message TEXT);
+             INSERT INTO logs VALUES ('a'), ('a');",
+        )
+        .await;
+
+        let snapshot = db
+            .adapter()
+            .await
+            .read_database(&TableFilter::default())
+            .await
+            .unwrap();
+
+        let table = &snapshot["logs"];
+        assert_eq!(table.key_columns, vec![ROWID_COLUMN]);
+        assert_eq!(table.rows[0][ROWID_COLUMN], json!(1));
+        assert_eq!(table.rows[1][ROWID_COLUMN], json!(2));
+    }
+
+    #[tokio::test]
+    async fn test_read_database_maps_storage_classes() {
+        let db = TestDatabase::create(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, anything);
+             INSERT INTO items VALUES (1, 42), (2, 1.5), (3, 'text'), (4, x'68656c6c6f'), (5, NULL);",
+        )
+        .await;
+
+        let snapshot = db
+            .adapter()
+            .await
+            .read_database(&TableFilter::default())
+            .await
+            .unwrap();
+
+        let values: Vec<_> = snapshot["items"]
+            .rows
+            .iter()
+            .map(|row| row["anything"].clone())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                json!(42),
+                json!(1.5),
+                json!("text"),
+                json!("MD5 Digest value: 5d41402abc4b2a76b9719d911017c592"),
+                Value::Null,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_database_renders_blobs_in_binary_format() {
+        let db = TestDatabase::create(
+            "CREATE TABLE tokens (id BLOB PRIMARY KEY, payload BLOB);
+             INSERT INTO tokens VALUES
+                 (x'6f1c1d560a524b8e9d1c1f2a3b4c5d6e', x'68656c6c6f');",
+        )
+        .await;
+        let binary_formats =
+            BinaryFormats::new(BinaryFormat::Hex, &["tokens.id=uuid".to_string()]).unwrap();
+
+        let snapshot = db
+            .adapter()
+            .await
+            .with_binary_formats(binary_formats)
+            .read_database(&TableFilter::default())
+            .await
+            .unwrap();
+
+        let row = &snapshot["tokens"].rows[0];
+        assert_eq!(row["id"], json!("6f1c1d56-0a52-4b8e-9d1c-1f2a3b4c5d6e"));
+        assert_eq!(row["payload"], json!("0x68656c6c6f"));
+    }
+
+    #[tokio::test]
+    async fn test_read_database_skips_filtered_tables() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY);
+             CREATE TABLE audit_log (id INTEGER PRIMARY KEY);
+             CREATE TABLE sessions (id INTEGER PRIMARY KEY);",
+        )
+        .await;
+        let filter = TableFilter::new(&[], &["*_log".to_string(), "sessions".to_string()]).unwrap();
+
+        let snapshot = db.adapter().await.read_database(&filter).await.unwrap();
+
+        assert_eq!(snapshot.keys().collect::<Vec<_>>(), vec!["users"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_tables_skips_internal_tables() {
+        let db = TestDatabase::create(
+            "CREATE TABLE b_table (id INTEGER PRIMARY KEY AUTOINCREMENT);
+             CREATE TABLE a_table (id INTEGER PRIMARY KEY);",
+        )
+        .await;
+
+        let tables = db.adapter().await.list_tables().await.unwrap();
+
+        assert_eq!(tables, vec!["a_table", "b_table"]);
+    }
+
+    #[tokio::test]
+    async fn test_table_schema_reads_indexes_and_foreign_keys() {
+        let db = TestDatabase::create(
+            "CREATE TABLE teams (id INTEGER PRIMARY KEY);
+             CREATE TABLE users (
+                 id INTEGER PRIMARY KEY,
+                 email TEXT NOT NULL DEFAULT '',
+                 team_id INTEGER REFERENCES teams (id)
+             );
+             CREATE UNIQUE INDEX users_email ON users (email);",
+        )
+        .await;
+
+        let schema = db
+            .adapter()
+            .await
+            .table_schema("users")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let definitions: Vec<_> = schema
+            .columns
+            .iter()
+            .map(|column| column.definition())
+            .collect();
+        assert_eq!(
+            definitions,
+            vec!["INTEGER", "TEXT NOT NULL DEFAULT ''", "INTEGER"]
+        );
+        let indexes: Vec<_> = schema
+            .indexes
+            .iter()
+            .map(|index| (index.name.as_str(), index.definition()))
+            .collect();
+        assert_eq!(
+            indexes,
+            vec![
+                (PRIMARY_KEY_NAME, "PRIMARY KEY (id)".to_string()),
+                ("users_email", "UNIQUE (email)".to_string()),
+            ]
+        );
+        assert_eq!(
+            schema.foreign_keys[0].definition(),
+            "(team_id) REFERENCES teams (id)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_records_changes_with_triggers() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB);
+             CREATE TABLE logs (message TEXT);
+             INSERT INTO users VALUES (1, 'John', NULL);",
+        )
+        .await;
+        let adapter = db.adapter().await;
+        let table_names = adapter.list_tables().await.unwrap();
+
+        adapter
+            .start_capture(CaptureMode::Triggers, &table_names)
+            .await
+            .unwrap();
+        db.execute(
+            "INSERT INTO logs VALUES ('[1]');
+             UPDATE users SET avatar = x'68656c6c6f' WHERE id = 1;
+             DELETE FROM users;",
+        )
+        .await;
+        let events = adapter.finish_capture().await.unwrap();
+
+        let changes: Vec<_> = events
+            .iter()
+            .map(|event| (event.sequence, event.table_name.as_str(), event.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (1, "logs", ChangeKind::Added),
+                (2, "users", ChangeKind::Modified),
+                (3, "users", ChangeKind::Deleted),
+            ]
+        );
+        let log = events[0].after.as_ref().unwrap();
+        assert_eq!(log[ROWID_COLUMN], json!(1));
+        assert_eq!(log["message"], json!("[1]"));
+        assert_eq!(events[1].before.as_ref().unwrap()["avatar"], Value::Null);
+        assert_eq!(
+            events[1].after.as_ref().unwrap()["avatar"],
+            json!("MD5 Digest value: 5d41402abc4b2a76b9719d911017c592")
+        );
+        assert_eq!(adapter.list_tables().await.unwrap(), vec!["logs", "users"]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_ignores_concurrent_writes() {
+        // WAL mode lets the write proceed while the read transaction is open
+        let db = TestDatabase::create(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'John');",
+        )
+        .await;
+        let adapter = db.adapter().await;
+
+        assert_eq!(adapter.begin_snapshot(4).await.unwrap(), 1);
+        db.execute("UPDATE users SET name = 'Jane'").await;
+        let table = adapter.read_table("users").await.unwrap();
+        assert_eq!(table.rows[0]["name"], json!("John"));
+        adapter.end_snapshot().await.unwrap();
+
+        let table = adapter.read_table("users").await.unwrap();
+        assert_eq!(table.rows[0]["name"], json!("Jane"));
+    }
+}
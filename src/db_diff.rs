@@ -1,12 +1,27 @@
+use crate::adapters::database::begin_consistent_snapshot;
 use crate::adapters::{DatabaseAdapter, OutputAdapter};
-use crate::error::Result;
-use crate::models::TableChanges;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::error::{DbbaError, Result};
+use crate::models::{
+    CaptureMode, ChangeEvent, ChangeKind, ColumnChange, CopyCount, DatabaseSnapshot, DiffOptions,
+    DiffStrategy, IgnoredColumns, Row, RowChange, RowOrder, SchemaChange, SchemaObject,
+    TableChanges, TableChecksum, TableFilter, TableSchema, TableSnapshot, TableStatus,
+};
+use crate::spill::{IdentifiedRow, SortedRuns, SpillTable};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use md5::{Digest, Md5};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
 
 /// Core database diff logic
 pub struct DbDiff<D: DatabaseAdapter, O: OutputAdapter> {
     db_adapter: D,
-    output_adapter: O,
+    report: DiffReport<O>,
+    table_filter: TableFilter,
+    jobs: usize,
+    consistent_snapshot: bool,
+    mode: CaptureMode,
 }
 
 impl<D: DatabaseAdapter, O: OutputAdapter> DbDiff<D, O> {
@@ -14,147 +29,1103 @@ impl<D: DatabaseAdapter, O: OutputAdapter> DbDiff<D, O> {
     pub fn new(db_adapter: D, output_adapter: O) -> Self {
         Self {
             db_adapter,
-            output_adapter,
+            report: DiffReport::new(output_adapter),
+            table_filter: TableFilter::default(),
+            jobs: 1,
+            consistent_snapshot: true,
+            mode: CaptureMode::Snapshot,
         }
     }
 
-    /// Execute the database diff process
+    /// Set the options used when comparing snapshots
+    pub fn with_options(mut self, options: DiffOptions) -> Self {
+        self.report = self.report.with_options(options);
+        self
+    }
+
+    /// Set the tables read for the snapshots
+    pub fn with_table_filter(mut self, table_filter: TableFilter) -> Self {
+        self.table_filter = table_filter;
+        self
+    }
+
+    /// Set the number of tables read concurrently
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Set whether all tables of a snapshot are read from one consistent state of the
+    /// database (default: true)
+    pub fn with_consistent_snapshot(mut self, consistent_snapshot: bool) -> Self {
+        self.consistent_snapshot = consistent_snapshot;
+        self
+    }
+
+    /// Set how the changes made by the use case are captured (default: snapshots)
+    pub fn with_mode(mut self, mode: CaptureMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Tables whose rows or definition changed in the last run
+    pub fn changed_tables(&self) -> &BTreeSet<String> {
+        self.report.changed_tables()
+    }
+
+    /// Execute the database diff process, waiting for the user to run the use case
     pub async fn execute(&mut self) -> Result<bool> {
+        let (has_changes, ()) = self
+            .execute_with(|| async {
+                println!("run usecase now. then press any key when done.");
+                // Off the runtime's threads, so Ctrl-C can still be handled meanwhile
+                tokio::task::spawn_blocking(wait_for_input)
+                    .await
+                    .map_err(|e| DbbaError::Unknown(e.to_string()))?
+            })
+            .await?;
+
+        Ok(has_changes)
+    }
+
+    /// Execute the database diff process, running `usecase` between the two snapshots.
+    ///
+    /// Returns whether changes were detected along with the use case's own result.
+    pub async fn execute_with<F, Fut, T>(&mut self, usecase: F) -> Result<(bool, T)>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.mode != CaptureMode::Snapshot {
+            return self.execute_capture(usecase).await;
+        }
+
         println!("now reading db...");
-        let before_db = self.db_adapter.read_database().await?;
+        let before_db = self.capture(None).await?;
 
-        println!("run usecase now. then press any key when done.");
-        Self::wait_for_input()?;
+        let outcome = usecase().await?;
 
         println!("now reading db...");
-        let after_db = self.db_adapter.read_database().await?;
+        let after_db = self.capture(Some(&before_db)).await?;
 
-        self.output_adapter.start_output()?;
+        self.report.start()?;
+        self.report
+            .compare_schemas(&before_db.schemas, &after_db.schemas);
+        let table_names: BTreeSet<_> = before_db
+            .tables
+            .keys()
+            .chain(after_db.tables.keys())
+            .collect();
+        for table_name in table_names {
+            if after_db.unchanged.contains(table_name) {
+                continue;
+            }
+            self.report.compare_rows(
+                table_name,
+                before_db.tables.get(table_name),
+                after_db.tables.get(table_name),
+            )?;
+        }
+        let has_changes = self.report.finish()?;
+        println!("done.");
 
-        let mut has_changes = false;
+        Ok((has_changes, outcome))
+    }
+
+    /// Let the database record the row changes made by `usecase` and report them in the
+    /// order they were made.
+    ///
+    /// If the use case fails or is interrupted with Ctrl-C, the capture is aborted so
+    /// nothing it installed is left in the database.
+    async fn execute_capture<F, Fut, T>(&mut self, usecase: F) -> Result<(bool, T)>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let table_names = self.table_names().await?;
+        let before_schemas = self.read_schemas(&table_names).await?;
+        // Read now so rows of tables the use case drops are still identified by key
+        let mut key_columns = BTreeMap::new();
+        for table_name in &table_names {
+            let columns = self.db_adapter.key_columns(table_name).await?;
+            key_columns.insert(table_name.clone(), columns);
+        }
+
+        // Take over Ctrl-C before installing anything, so it can't kill us halfway
+        let interrupted = tokio::signal::ctrl_c();
+        tokio::pin!(interrupted);
+        let _ = futures_util::poll!(interrupted.as_mut());
+
+        println!("now recording changes ({})...", self.mode);
+        if let Err(e) = self.db_adapter.start_capture(self.mode, &table_names).await {
+            self.db_adapter.abort_capture().await?;
+            return Err(e);
+        }
+
+        let outcome = tokio::select! {
+            outcome = usecase() => outcome,
+            _ = interrupted => Err(DbbaError::Interrupted),
+        };
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                println!("aborting capture...");
+                self.db_adapter.abort_capture().await?;
+                return Err(e);
+            }
+        };
+
+        println!("now reading changes...");
+        let mut tables: BTreeMap<String, Vec<ChangeEvent>> = BTreeMap::new();
+        for event in self.db_adapter.finish_capture().await? {
+            if self.table_filter.matches(&event.table_name) {
+                tables
+                    .entry(event.table_name.clone())
+                    .or_default()
+                    .push(event);
+            }
+        }
+        let after_table_names = self.table_names().await?;
+        let after_schemas = self.read_schemas(&after_table_names).await?;
+
+        // Tables created or dropped by the use case are reported even without events
+        for table_name in table_names.iter().chain(&after_table_names) {
+            tables.entry(table_name.clone()).or_default();
+        }
+
+        self.report.start()?;
+        self.report.compare_schemas(&before_schemas, &after_schemas);
+        for (table_name, events) in &tables {
+            let status = TableStatus::of(
+                table_names.contains(table_name),
+                after_table_names.contains(table_name),
+            );
+            let key_columns = match key_columns.get(table_name) {
+                Some(columns) => columns.clone(),
+                None => self.db_adapter.key_columns(table_name).await?,
+            };
+            self.report
+                .write_events(table_name, status, &key_columns, events)?;
+        }
+        let has_changes = self.report.finish()?;
+        println!("done.");
+
+        Ok((has_changes, outcome))
+    }
+
+    /// Names of the selected tables
+    async fn table_names(&self) -> Result<Vec<String>> {
+        let mut table_names = self.db_adapter.list_tables().await?;
+        table_names.retain(|table_name| self.table_filter.matches(table_name));
+        Ok(table_names)
+    }
+
+    /// Read the definitions of the given tables, if the database adapter reads them
+    async fn read_schemas(&self, table_names: &[String]) -> Result<BTreeMap<String, TableSchema>> {
+        let mut schemas = BTreeMap::new();
+        for table_name in table_names {
+            if let Some(schema) = self.db_adapter.table_schema(table_name).await? {
+                schemas.insert(table_name.clone(), schema);
+            }
+        }
+        Ok(schemas)
+    }
+
+    /// Read every selected table, from one consistent snapshot unless disabled
+    async fn capture(&self, before: Option<&Capture>) -> Result<Capture> {
+        if !self.consistent_snapshot {
+            return self.capture_tables(before, self.jobs).await;
+        }
+
+        let jobs = begin_consistent_snapshot(&self.db_adapter, self.jobs).await?;
+        let capture = self.capture_tables(before, jobs).await;
+        self.db_adapter.end_snapshot().await?;
+        capture
+    }
+
+    /// Stream every selected table into spillable row storage, reading up to `jobs`
+    /// tables at a time.
+    ///
+    /// Tables whose checksum is the same as in `before` are not read again.
+    async fn capture_tables(&self, before: Option<&Capture>, jobs: usize) -> Result<Capture> {
+        let table_names = self.table_names().await?;
+
+        let tables: Vec<_> = stream::iter(table_names)
+            .map(|table_name| self.capture_table(table_name, before))
+            .buffer_unordered(jobs)
+            .try_collect()
+            .await?;
+
+        // Tables finish in any order; the maps keep the report independent of it
+        let mut capture = Capture::default();
+        for (table_name, table, schema) in tables {
+            if let Some(schema) = schema {
+                capture.schemas.insert(table_name.clone(), schema);
+            }
+            match table {
+                CapturedTable::Unchanged => {
+                    capture.unchanged.insert(table_name);
+                }
+                CapturedTable::Read(rows, checksum) => {
+                    if let Some(checksum) = checksum {
+                        capture.checksums.insert(table_name.clone(), checksum);
+                    }
+                    capture.tables.insert(table_name, rows);
+                }
+            }
+        }
+
+        Ok(capture)
+    }
+
+    /// Read one table's definition, and its rows unless its checksum is the same as in
+    /// `before`.
+    ///
+    /// Rows of tables already in `before` are identified by the key columns they had
+    /// then, so both snapshots of a table are partitioned alike.
+    async fn capture_table(
+        &self,
+        table_name: String,
+        before: Option<&Capture>,
+    ) -> Result<(String, CapturedTable, Option<TableSchema>)> {
+        // Definitions are always read: changing them rarely changes the checksum
+        let schema = self.db_adapter.table_schema(&table_name).await?;
+        let checksum = self.db_adapter.table_checksum(&table_name).await?;
+        if checksum.is_some()
+            && before.and_then(|before| before.checksums.get(&table_name)) == checksum.as_ref()
+        {
+            return Ok((table_name, CapturedTable::Unchanged, schema));
+        }
+
+        let key_columns = match before.and_then(|before| before.tables.get(&table_name)) {
+            Some(table) => table.key_columns().to_vec(),
+            None => self.db_adapter.key_columns(&table_name).await?,
+        };
+        let identity = self.report.row_identity(&table_name, &key_columns);
+        let mut rows = SpillTable::new(key_columns.clone());
+        self.db_adapter
+            .scan_table(&table_name, &mut |row| rows.push(identity.of(&row), row))
+            .await?;
+        rows.finish()?;
+
+        Ok((table_name, CapturedTable::Read(rows, checksum), schema))
+    }
+
+    /// Compare two snapshots and write the changes through the output adapter
+    pub fn write_report(
+        &mut self,
+        before_db: &DatabaseSnapshot,
+        after_db: &DatabaseSnapshot,
+    ) -> Result<bool> {
+        self.report.start()?;
 
         // Sort table names for consistent output order
-        let mut table_names: Vec<_> = before_db.keys().collect();
-        table_names.sort();
+        let table_names: BTreeSet<_> = before_db.keys().chain(after_db.keys()).collect();
+        for table_name in table_names {
+            self.report.compare_table(
+                table_name,
+                before_db.get(table_name),
+                after_db.get(table_name),
+            )?;
+        }
+
+        self.report.finish()
+    }
+}
+
+/// Tables read from the database for one side of a comparison
+#[derive(Default)]
+struct Capture {
+    tables: BTreeMap<String, SpillTable>,
+    checksums: HashMap<String, TableChecksum>,
+    /// Tables not read because their checksum matched the previous capture
+    unchanged: HashSet<String>,
+    /// Definitions of all tables, if the database adapter reads them
+    schemas: BTreeMap<String, TableSchema>,
+}
+
+/// Outcome of reading one table for a capture
+enum CapturedTable {
+    /// The checksum matched the previous capture, so the rows were not read
+    Unchanged,
+    /// Rows of the table, with its checksum if the database computes one
+    Read(SpillTable, Option<TableChecksum>),
+}
+
+/// Writes the changes between snapshots through an output adapter, one table at a time
+pub struct DiffReport<O: OutputAdapter> {
+    output_adapter: O,
+    options: DiffOptions,
+    /// Tables with changed rows or definitions
+    changed_tables: BTreeSet<String>,
+    /// Changes to table definitions, written when the report finishes
+    schema_changes: Vec<SchemaChange>,
+}
+
+impl<O: OutputAdapter> DiffReport<O> {
+    pub fn new(output_adapter: O) -> Self {
+        Self {
+            output_adapter,
+            options: DiffOptions::default(),
+            changed_tables: BTreeSet::new(),
+            schema_changes: Vec::new(),
+        }
+    }
 
+    /// Set the options used when comparing tables
+    pub fn with_options(mut self, options: DiffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Start the report
+    pub fn start(&mut self) -> Result<()> {
+        self.changed_tables.clear();
+        self.schema_changes.clear();
+        self.output_adapter.start_output()?;
+        self.output_adapter.write_timezone(self.options.timezone)
+    }
+
+    /// Compare the definitions of a table; a table missing before was created and one
+    /// missing after was dropped
+    pub fn compare_schema(
+        &mut self,
+        table_name: &str,
+        before: Option<&TableSchema>,
+        after: Option<&TableSchema>,
+    ) {
+        self.schema_changes
+            .extend(schema_changes(table_name, before, after));
+    }
+
+    /// Compare the definitions of all tables in either `before` or `after`
+    pub fn compare_schemas(
+        &mut self,
+        before: &BTreeMap<String, TableSchema>,
+        after: &BTreeMap<String, TableSchema>,
+    ) {
+        let table_names: BTreeSet<_> = before.keys().chain(after.keys()).collect();
         for table_name in table_names {
-            let before_records = before_db.get(table_name).unwrap();
-            let after_records = after_db.get(table_name).cloned().unwrap_or_default();
+            self.compare_schema(table_name, before.get(table_name), after.get(table_name));
+        }
+    }
 
-            // Convert records to JSON strings indexed by ID
-            let before_map = Self::records_to_json_map(before_records);
-            let after_map = Self::records_to_json_map(&after_records);
+    /// Compare one table and write its changes; a table missing on one side was created
+    /// or dropped and is compared as empty there
+    pub fn compare_table(
+        &mut self,
+        table_name: &str,
+        before_table: Option<&TableSnapshot>,
+        after_table: Option<&TableSnapshot>,
+    ) -> Result<()> {
+        let Some(key_columns) = before_table.or(after_table).map(|table| &table.key_columns) else {
+            return Ok(());
+        };
+        let identity = self.row_identity(table_name, key_columns);
+        let collect = |table: &TableSnapshot| -> Result<SpillTable> {
+            let mut rows = SpillTable::new(key_columns.clone()).with_threshold(usize::MAX);
+            for row in &table.rows {
+                rows.push(identity.of(row), row.clone())?;
+            }
+            Ok(rows)
+        };
+        let before_rows = before_table.map(collect).transpose()?;
+        let after_rows = after_table.map(collect).transpose()?;
 
-            // Detect changes
-            let changes = detect_changes(&before_map, &after_map);
+        self.compare_rows(table_name, before_rows.as_ref(), after_rows.as_ref())
+    }
 
-            if changes.has_changes() {
-                has_changes = true;
+    /// Compare one table whose rows were collected with `row_identity`, one partition
+    /// at a time; a table missing on one side was created or dropped and is compared
+    /// as empty there
+    pub fn compare_rows(
+        &mut self,
+        table_name: &str,
+        before_table: Option<&SpillTable>,
+        after_table: Option<&SpillTable>,
+    ) -> Result<()> {
+        let Some(key_columns) = before_table.or(after_table).map(SpillTable::key_columns) else {
+            return Ok(());
+        };
+        let strategy = self.strategy(table_name, key_columns);
+        let status = TableStatus::of(before_table.is_some(), after_table.is_some());
 
-                self.output_adapter.write_title(table_name)?;
+        let ignored_columns = &self.options.ignored_columns;
+        let is_ignored = |column: &str| ignored_columns.is_ignored(table_name, column);
 
-                // Get all IDs that have changes
-                let all_ids: HashSet<String> = changes
-                    .deleted_ids
-                    .iter()
-                    .chain(changes.added_ids.iter())
-                    .chain(changes.modified_ids.iter())
-                    .cloned()
-                    .collect();
+        let partition_count = before_table
+            .into_iter()
+            .chain(after_table)
+            .map(SpillTable::partition_count)
+            .max()
+            .unwrap_or(1);
+        // Changed rows are sorted one partition at a time and merged as they are
+        // written, spilling them past the threshold of the rows they were found in
+        let threshold = before_table
+            .into_iter()
+            .chain(after_table)
+            .map(SpillTable::threshold)
+            .min()
+            .unwrap_or(usize::MAX);
+        let row_order = self.options.row_order;
+        let mut has_changes = false;
+        let mut row_changes = SortedRuns::new().with_threshold(threshold);
+        for index in 0..partition_count {
+            let before_records = match before_table {
+                Some(table) => table.partition(index, partition_count)?,
+                None => Cow::Borrowed(&[][..]),
+            };
+            let after_records = match after_table {
+                Some(table) => table.partition(index, partition_count)?,
+                None => Cow::Borrowed(&[][..]),
+            };
 
-                for id in all_ids {
-                    let left = before_map.get(&id).map(|s| s.as_str()).unwrap_or("");
-                    let right = after_map.get(&id).map(|s| s.as_str()).unwrap_or("");
+            // Index records by row key (or content hash) and detect changes
+            let (before_rows, after_rows, mut changes) = match strategy {
+                DiffStrategy::Key => {
+                    let before_rows = Self::records_by_identity(&before_records);
+                    let after_rows = Self::records_by_identity(&after_records);
+                    let changes = detect_changes(&before_rows, &after_rows, is_ignored);
+                    (before_rows, after_rows, changes)
+                }
+                DiffStrategy::Multiset => {
+                    let (before_rows, before_counts) =
+                        Self::records_to_content_map(&before_records);
+                    let (after_rows, after_counts) = Self::records_to_content_map(&after_records);
+                    let changes = detect_multiset_changes(&before_counts, &after_counts);
+                    (before_rows, after_rows, changes)
+                }
+            };
+
+            // Even an empty created or dropped table gets a section
+            changes.status = status;
+            if !changes.has_changes() {
+                continue;
+            }
+            has_changes = true;
 
-                    let (left_html, right_html) = self.output_adapter.generate_diff(left, right);
-                    self.output_adapter
-                        .write_diff_section(&left_html, &right_html)?;
+            // Get all IDs that have changes
+            let all_ids = changes
+                .deleted_ids
+                .iter()
+                .chain(changes.added_ids.iter())
+                .chain(changes.modified_ids.iter())
+                .cloned();
+
+            let mut partition_changes = Vec::new();
+            for id in all_ids {
+                let mut before = before_rows.get(&id).copied();
+                let mut after = after_rows.get(&id).copied();
+                let copy_count = changes.copy_counts.get(&id).copied();
+
+                // Identical copies have identical content, so show only the side that changed
+                if let Some(count) = copy_count {
+                    if count.after > count.before {
+                        before = None;
+                    } else {
+                        after = None;
+                    }
                 }
 
-                self.output_adapter.close_section()?;
+                let kind = match (before, after) {
+                    (None, _) => ChangeKind::Added,
+                    (_, None) => ChangeKind::Deleted,
+                    _ => ChangeKind::Modified,
+                };
+                let changed_columns = changes.column_changes.remove(&id).unwrap_or_default();
+                partition_changes.push(RowChange {
+                    key: id,
+                    kind,
+                    before: before.cloned(),
+                    after: after.cloned(),
+                    changed_columns,
+                    copy_count,
+                    sequence: None,
+                });
+            }
+            sort_row_changes(&mut partition_changes, key_columns, row_order);
+            row_changes.push(partition_changes)?;
+        }
+
+        if !has_changes {
+            return Ok(());
+        }
+        self.changed_tables.insert(table_name.to_string());
+
+        self.output_adapter.write_title(table_name)?;
+        if status != TableStatus::Existing {
+            self.output_adapter.write_table_status(status)?;
+        }
+        if strategy == DiffStrategy::Multiset {
+            let reason = if self.is_forced_multiset(table_name) {
+                "selected with --multiset-table"
+            } else {
+                "table has no primary key or unique NOT NULL key"
+            };
+            self.output_adapter
+                .write_note(&format!("Rows compared by content ({})", reason))?;
+        }
+        let row_changes =
+            row_changes.merge(|a, b| compare_row_changes(a, b, key_columns, row_order))?;
+        for change in row_changes {
+            Self::write_row_change(&mut self.output_adapter, &self.options, table_name, change?)?;
+        }
+        self.output_adapter.close_section()
+    }
+
+    /// Write the row changes recorded for one table, in the order they were made.
+    ///
+    /// Updates that only changed ignored columns are left out.
+    pub fn write_events(
+        &mut self,
+        table_name: &str,
+        status: TableStatus,
+        key_columns: &[String],
+        events: &[ChangeEvent],
+    ) -> Result<()> {
+        let ignored_columns = &self.options.ignored_columns;
+        let is_ignored = |column: &str| ignored_columns.is_ignored(table_name, column);
+        let identity = self.row_identity(table_name, key_columns);
+
+        let mut changes = Vec::new();
+        for event in events {
+            let changed_columns = match (&event.before, &event.after) {
+                (Some(before), Some(after)) => {
+                    let columns = changed_columns(before, after, is_ignored);
+                    if columns.iter().all(|column| column.ignored) {
+                        continue;
+                    }
+                    columns
+                }
+                _ => Vec::new(),
+            };
+            let Some(row) = event.after.as_ref().or(event.before.as_ref()) else {
+                continue;
+            };
+            changes.push(RowChange {
+                key: identity.of(row),
+                kind: event.kind,
+                before: event.before.clone(),
+                after: event.after.clone(),
+                changed_columns,
+                copy_count: None,
+                sequence: Some(event.sequence),
+            });
+        }
+
+        if changes.is_empty() && status == TableStatus::Existing {
+            return Ok(());
+        }
+        self.changed_tables.insert(table_name.to_string());
+
+        self.output_adapter.write_title(table_name)?;
+        if status != TableStatus::Existing {
+            self.output_adapter.write_table_status(status)?;
+        }
+        if !changes.is_empty() {
+            self.output_adapter
+                .write_note("Changes listed in the order they were made")?;
+        }
+        for change in changes {
+            Self::write_row_change(&mut self.output_adapter, &self.options, table_name, change)?;
+        }
+        self.output_adapter.close_section()
+    }
+
+    /// Write a changed row, leaving ignored columns out unless asked to show them
+    fn write_row_change(
+        output_adapter: &mut O,
+        options: &DiffOptions,
+        table_name: &str,
+        mut change: RowChange,
+    ) -> Result<()> {
+        if !options.show_ignored_columns {
+            for row in change.before.iter_mut().chain(change.after.iter_mut()) {
+                row.retain(|column, _| !options.ignored_columns.is_ignored(table_name, column));
             }
+            change.changed_columns.retain(|column| !column.ignored);
         }
 
+        output_adapter.write_row_change(&change)
+    }
+
+    /// Finish the report, returning whether any table changed
+    pub fn finish(&mut self) -> Result<bool> {
+        if !self.schema_changes.is_empty() {
+            self.changed_tables.extend(
+                self.schema_changes
+                    .iter()
+                    .map(|change| change.table_name.clone()),
+            );
+            self.output_adapter
+                .write_schema_changes(&self.schema_changes)?;
+        }
+
+        let has_changes = !self.changed_tables.is_empty();
         if !has_changes {
             self.output_adapter.write_no_diff_message()?;
         }
 
         self.output_adapter.end_output()?;
-        println!("done.");
 
         Ok(has_changes)
     }
 
-    /// Convert records to a HashMap of ID -> JSON string
-    fn records_to_json_map(
-        records: &[BTreeMap<String, serde_json::Value>],
-    ) -> HashMap<String, String> {
+    /// Tables whose rows or definition changed, once the report is finished
+    pub fn changed_tables(&self) -> &BTreeSet<String> {
+        &self.changed_tables
+    }
+
+    /// How rows of a table with the given key columns are identified
+    pub fn row_identity<'a>(
+        &'a self,
+        table_name: &'a str,
+        key_columns: &'a [String],
+    ) -> RowIdentity<'a> {
+        RowIdentity {
+            table_name,
+            key_columns,
+            strategy: self.strategy(table_name, key_columns),
+            ignored_columns: &self.options.ignored_columns,
+        }
+    }
+
+    fn strategy(&self, table_name: &str, key_columns: &[String]) -> DiffStrategy {
+        if key_columns.is_empty() || self.is_forced_multiset(table_name) {
+            DiffStrategy::Multiset
+        } else {
+            DiffStrategy::Key
+        }
+    }
+
+    fn is_forced_multiset(&self, table_name: &str) -> bool {
+        self.options.multiset_tables.iter().any(|t| t == table_name)
+    }
+
+    /// Index records by their identity (row key)
+    fn records_by_identity(records: &[IdentifiedRow]) -> HashMap<String, &Row> {
         records
             .iter()
-            .filter_map(|record| {
-                let id = record
-                    .get("id")
-                    .and_then(|v| match v {
-                        serde_json::Value::Number(n) => Some(n.to_string()),
-                        serde_json::Value::String(s) => Some(s.clone()),
-                        _ => None,
-                    })
-                    .unwrap_or_else(|| "unknown".to_string());
-
-                serde_json::to_string_pretty(record)
-                    .ok()
-                    .map(|json| (id, json))
+            .map(|(identity, record)| (identity.clone(), record))
+            .collect()
+    }
+
+    /// Index records by their identity (content hash), along with the number of
+    /// identical copies of each content
+    fn records_to_content_map(
+        records: &[IdentifiedRow],
+    ) -> (HashMap<String, &Row>, HashMap<String, usize>) {
+        let mut contents = HashMap::new();
+        let mut counts = HashMap::new();
+
+        for (hash, record) in records {
+            *counts.entry(hash.clone()).or_insert(0) += 1;
+            contents.entry(hash.clone()).or_insert(record);
+        }
+
+        (contents, counts)
+    }
+}
+
+/// Computes the identity of a table's rows: the row key, or for tables compared by
+/// content a hash of the row without its ignored columns
+pub struct RowIdentity<'a> {
+    table_name: &'a str,
+    key_columns: &'a [String],
+    strategy: DiffStrategy,
+    ignored_columns: &'a IgnoredColumns,
+}
+
+impl RowIdentity<'_> {
+    /// Identity of a row
+    pub fn of(&self, record: &Row) -> String {
+        match self.strategy {
+            DiffStrategy::Key => row_key(record, self.key_columns),
+            DiffStrategy::Multiset => {
+                let content: BTreeMap<_, _> = record
+                    .iter()
+                    .filter(|(column, _)| !self.ignored_columns.is_ignored(self.table_name, column))
+                    .collect();
+                let json = serde_json::to_string_pretty(&content).unwrap_or_default();
+                format!("{:x}", Md5::digest(json.as_bytes()))
+            }
+        }
+    }
+}
+
+/// Wait for user input
+fn wait_for_input() -> Result<()> {
+    use std::io::{stdin, BufRead};
+    let stdin = stdin();
+    let mut lines = stdin.lock().lines();
+    lines.next();
+    Ok(())
+}
+
+/// Build the identity of a record from its key columns.
+///
+/// A single key column yields its value as JSON (e.g. `42` or `"a1b2"`, so the number
+/// and the string never collide), a composite key yields a JSON array of the values
+/// (e.g. `[42,"admin"]`). Tables without a key are identified by their whole content.
+pub(crate) fn row_key(record: &Row, key_columns: &[String]) -> String {
+    match key_columns {
+        [] => serde_json::to_string(record).unwrap_or_default(),
+        [column] => serde_json::to_string(record.get(column).unwrap_or(&serde_json::Value::Null))
+            .unwrap_or_default(),
+        columns => {
+            let values: Vec<_> = columns
+                .iter()
+                .map(|c| record.get(c).unwrap_or(&serde_json::Value::Null))
+                .collect();
+            serde_json::to_string(&values).unwrap_or_default()
+        }
+    }
+}
+
+/// Sort changed rows by key, comparing numbers by value and numbered strings
+/// naturally, after grouping them by kind if asked to
+fn sort_row_changes(changes: &mut [RowChange], key_columns: &[String], order: RowOrder) {
+    changes.sort_by(|a, b| compare_row_changes(a, b, key_columns, order));
+}
+
+/// Order of two changed rows in the report, as sorted by `sort_row_changes`
+fn compare_row_changes(
+    a: &RowChange,
+    b: &RowChange,
+    key_columns: &[String],
+    order: RowOrder,
+) -> Ordering {
+    let kind_rank = |change: &RowChange| match (order, change.kind) {
+        (RowOrder::Key, _) => 0,
+        (RowOrder::Kind, ChangeKind::Added) => 0,
+        (RowOrder::Kind, ChangeKind::Modified) => 1,
+        (RowOrder::Kind, ChangeKind::Deleted) => 2,
+    };
+    let key_values = |change: &RowChange| -> Vec<serde_json::Value> {
+        let row = change.after.as_ref().or(change.before.as_ref());
+        key_columns
+            .iter()
+            .map(|column| {
+                row.and_then(|row| row.get(column))
+                    .cloned()
+                    .unwrap_or_default()
             })
             .collect()
+    };
+    kind_rank(a).cmp(&kind_rank(b)).then_with(|| {
+        let (a_values, b_values) = (key_values(a), key_values(b));
+        a_values
+            .iter()
+            .zip(&b_values)
+            .map(|(a, b)| compare_values(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| natural_cmp(&a.key, &b.key))
+    })
+}
+
+/// Order JSON values: nulls first, then booleans, numbers and strings
+fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> Ordering {
+    use serde_json::Value;
+    let rank = |value: &Value| match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) | Value::Object(_) => 4,
+    };
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => match (a.as_u64(), b.as_u64()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a
+                    .as_f64()
+                    .partial_cmp(&b.as_f64())
+                    .unwrap_or(Ordering::Equal),
+            },
+        },
+        (Value::String(a), Value::String(b)) => natural_cmp(a, b),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_string().cmp(&b.to_string())),
     }
+}
 
-    /// Wait for user input
-    fn wait_for_input() -> Result<()> {
-        use std::io::{stdin, BufRead};
-        let stdin = stdin();
-        let mut lines = stdin.lock().lines();
-        lines.next();
-        Ok(())
+/// Compare strings with runs of digits compared by their numeric value, so "item2"
+/// comes before "item10"
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(a_char), Some(b_char)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let a_end = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (a_digits, b_digits) = (
+                a[..a_end].trim_start_matches('0'),
+                b[..b_end].trim_start_matches('0'),
+            );
+            let ordering = a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits))
+                .then_with(|| a_end.cmp(&b_end));
+            if ordering.is_ne() {
+                return ordering;
+            }
+            (a, b) = (&a[a_end..], &b[b_end..]);
+        } else {
+            if a_char != b_char {
+                return a_char.cmp(&b_char);
+            }
+            (a, b) = (&a[a_char.len_utf8()..], &b[b_char.len_utf8()..]);
+        }
     }
 }
 
-/// Detect changes between before and after maps
+/// Columns whose values differ between two versions of a row, in column order
+fn changed_columns(
+    before: &Row,
+    after: &Row,
+    is_ignored: impl Fn(&str) -> bool,
+) -> Vec<ColumnChange> {
+    let columns: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    columns
+        .into_iter()
+        .filter(|column| before.get(*column) != after.get(*column))
+        .map(|column| {
+            let value = |row: &Row| row.get(column).cloned().unwrap_or_default();
+            ColumnChange {
+                ignored: is_ignored(column),
+                ..ColumnChange::new(column, value(before), value(after))
+            }
+        })
+        .collect()
+}
+
+/// Changes between two definitions of a table; `None` means the table doesn't exist
+fn schema_changes(
+    table_name: &str,
+    before: Option<&TableSchema>,
+    after: Option<&TableSchema>,
+) -> Vec<SchemaChange> {
+    let change = |object, name: Option<&str>, kind, before, after| SchemaChange {
+        table_name: table_name.to_string(),
+        object,
+        name: name.map(str::to_string),
+        kind,
+        before,
+        after,
+    };
+
+    let (before, after) = match (before, after) {
+        (Some(before), Some(after)) => (before, after),
+        (None, Some(after)) => {
+            let definition = Some(after.definition());
+            return vec![change(
+                SchemaObject::Table,
+                None,
+                ChangeKind::Added,
+                None,
+                definition,
+            )];
+        }
+        (Some(before), None) => {
+            let definition = Some(before.definition());
+            return vec![change(
+                SchemaObject::Table,
+                None,
+                ChangeKind::Deleted,
+                definition,
+                None,
+            )];
+        }
+        (None, None) => return Vec::new(),
+    };
+
+    let mut changes = Vec::new();
+    let mut compare = |object, before: Vec<(&str, String)>, after: Vec<(&str, String)>| {
+        let after_names: HashMap<_, _> = after.iter().cloned().collect();
+        let before_names: HashSet<_> = before.iter().map(|(name, _)| *name).collect();
+        for (name, definition) in &before {
+            match after_names.get(name) {
+                None => changes.push(change(
+                    object,
+                    Some(name),
+                    ChangeKind::Deleted,
+                    Some(definition.clone()),
+                    None,
+                )),
+                Some(after) if after != definition => changes.push(change(
+                    object,
+                    Some(name),
+                    ChangeKind::Modified,
+                    Some(definition.clone()),
+                    Some(after.clone()),
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, definition) in after {
+            if !before_names.contains(name) {
+                changes.push(change(
+                    object,
+                    Some(name),
+                    ChangeKind::Added,
+                    None,
+                    Some(definition),
+                ));
+            }
+        }
+    };
+
+    compare(
+        SchemaObject::Column,
+        definitions(&before.columns, |c| (&c.name, c.definition())),
+        definitions(&after.columns, |c| (&c.name, c.definition())),
+    );
+    compare(
+        SchemaObject::Index,
+        definitions(&before.indexes, |i| (&i.name, i.definition())),
+        definitions(&after.indexes, |i| (&i.name, i.definition())),
+    );
+    compare(
+        SchemaObject::ForeignKey,
+        definitions(&before.foreign_keys, |f| (&f.name, f.definition())),
+        definitions(&after.foreign_keys, |f| (&f.name, f.definition())),
+    );
+    changes
+}
+
+/// Names and human readable definitions of table objects
+fn definitions<T>(items: &[T], definition: impl Fn(&T) -> (&str, String)) -> Vec<(&str, String)> {
+    items.iter().map(definition).collect()
+}
+
+/// Detect changes between before and after rows indexed by row key.
+///
+/// A row is modified only if a column that is not ignored changed.
 fn detect_changes(
-    before: &HashMap<String, String>,
-    after: &HashMap<String, String>,
+    before: &HashMap<String, &Row>,
+    after: &HashMap<String, &Row>,
+    is_ignored: impl Fn(&str) -> bool,
 ) -> TableChanges {
     let before_keys: HashSet<_> = before.keys().cloned().collect();
     let after_keys: HashSet<_> = after.keys().cloned().collect();
 
     let deleted_ids: Vec<String> = before_keys.difference(&after_keys).cloned().collect();
     let added_ids: Vec<String> = after_keys.difference(&before_keys).cloned().collect();
-    let modified_ids: Vec<String> = before_keys
-        .intersection(&after_keys)
-        .filter(|id| before.get(*id) != after.get(*id))
-        .cloned()
-        .collect();
+    let mut modified_ids = Vec::new();
+    let mut column_changes = HashMap::new();
+    for id in before_keys.intersection(&after_keys) {
+        let columns = changed_columns(before[id], after[id], &is_ignored);
+        if columns.iter().any(|column| !column.ignored) {
+            modified_ids.push(id.clone());
+            column_changes.insert(id.clone(), columns);
+        }
+    }
 
     TableChanges {
         table_name: String::new(),
+        status: TableStatus::Existing,
+        strategy: DiffStrategy::Key,
         deleted_ids,
         added_ids,
         modified_ids,
+        copy_counts: HashMap::new(),
+        column_changes,
+    }
+}
+
+/// Detect changes between before and after copy counts of each row content
+fn detect_multiset_changes(
+    before: &HashMap<String, usize>,
+    after: &HashMap<String, usize>,
+) -> TableChanges {
+    let mut deleted_ids = Vec::new();
+    let mut added_ids = Vec::new();
+    let mut copy_counts = HashMap::new();
+
+    let hashes: HashSet<_> = before.keys().chain(after.keys()).collect();
+    for hash in hashes {
+        let count = CopyCount {
+            before: before.get(hash).copied().unwrap_or(0),
+            after: after.get(hash).copied().unwrap_or(0),
+        };
+        if count.before > count.after {
+            deleted_ids.push(hash.clone());
+        } else if count.after > count.before {
+            added_ids.push(hash.clone());
+        } else {
+            continue;
+        }
+        copy_counts.insert(hash.clone(), count);
+    }
+
+    TableChanges {
+        table_name: String::new(),
+        status: TableStatus::Existing,
+        strategy: DiffStrategy::Multiset,
+        deleted_ids,
+        added_ids,
+        modified_ids: Vec::new(),
+        copy_counts,
+        column_changes: HashMap::new(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::adapters::sqlite::tests::TestDatabase;
+    use crate::adapters::{HtmlOutputAdapter, JsonOutputAdapter};
+    use crate::models::ColumnDefinition;
+    use serde_json::json;
+
+    fn record(pairs: &[(&str, serde_json::Value)]) -> Row {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_row_key_single_column() {
+        let r = record(&[("uuid", json!("a1b2")), ("name", json!("John"))]);
+        assert_eq!(row_key(&r, &["uuid".to_string()]), r#""a1b2""#);
+
+        let r = record(&[("id", json!(42))]);
+        assert_eq!(row_key(&r, &["id".to_string()]), "42");
+    }
+
+    #[test]
+    fn test_row_key_keeps_value_types_apart() {
+        let key = |value| row_key(&record(&[("id", value)]), &["id".to_string()]);
+        assert_ne!(key(json!("42")), key(json!(42)));
+        assert_ne!(key(json!("null")), key(json!(null)));
+        assert_eq!(key(json!(null)), row_key(&record(&[]), &["id".to_string()]));
+    }
+
+    #[test]
+    fn test_row_key_composite_columns() {
+        let r = record(&[("user_id", json!(1)), ("role_id", json!(2))]);
+        let key = row_key(&r, &["user_id".to_string(), "role_id".to_string()]);
+        assert_eq!(key, "[1,2]");
+    }
 
     #[test]
     fn test_detect_changes_no_changes() {
+        let john = record(&[("id", json!(1)), ("name", json!("John"))]);
         let mut before = HashMap::new();
-        before.insert("1".to_string(), r#"{"id":1,"name":"John"}"#.to_string());
+        before.insert("1".to_string(), &john);
 
         let mut after = HashMap::new();
-        after.insert("1".to_string(), r#"{"id":1,"name":"John"}"#.to_string());
+        after.insert("1".to_string(), &john);
 
-        let changes = detect_changes(&before, &after);
+        let changes = detect_changes(&before, &after, |_| false);
 
         assert_eq!(changes.deleted_ids.len(), 0);
         assert_eq!(changes.added_ids.len(), 0);
@@ -164,12 +1135,13 @@ mod tests {
 
     #[test]
     fn test_detect_changes_with_additions() {
+        let john = record(&[("id", json!(1)), ("name", json!("John"))]);
         let before = HashMap::new();
 
         let mut after = HashMap::new();
-        after.insert("1".to_string(), r#"{"id":1,"name":"John"}"#.to_string());
+        after.insert("1".to_string(), &john);
 
-        let changes = detect_changes(&before, &after);
+        let changes = detect_changes(&before, &after, |_| false);
 
         assert_eq!(changes.added_ids.len(), 1);
         assert!(changes.added_ids.contains(&"1".to_string()));
@@ -178,12 +1150,13 @@ mod tests {
 
     #[test]
     fn test_detect_changes_with_deletions() {
+        let john = record(&[("id", json!(1)), ("name", json!("John"))]);
         let mut before = HashMap::new();
-        before.insert("1".to_string(), r#"{"id":1,"name":"John"}"#.to_string());
+        before.insert("1".to_string(), &john);
 
         let after = HashMap::new();
 
-        let changes = detect_changes(&before, &after);
+        let changes = detect_changes(&before, &after, |_| false);
 
         assert_eq!(changes.deleted_ids.len(), 1);
         assert!(changes.deleted_ids.contains(&"1".to_string()));
@@ -192,16 +1165,580 @@ mod tests {
 
     #[test]
     fn test_detect_changes_with_modifications() {
+        let john = record(&[("id", json!(1)), ("name", json!("John"))]);
+        let jane = record(&[("id", json!(1)), ("name", json!("Jane"))]);
         let mut before = HashMap::new();
-        before.insert("1".to_string(), r#"{"id":1,"name":"John"}"#.to_string());
+        before.insert("1".to_string(), &john);
 
         let mut after = HashMap::new();
-        after.insert("1".to_string(), r#"{"id":1,"name":"Jane"}"#.to_string());
+        after.insert("1".to_string(), &jane);
 
-        let changes = detect_changes(&before, &after);
+        let changes = detect_changes(&before, &after, |_| false);
 
         assert_eq!(changes.modified_ids.len(), 1);
         assert!(changes.modified_ids.contains(&"1".to_string()));
         assert!(changes.has_changes());
+        assert_eq!(
+            changes.column_changes["1"],
+            vec![ColumnChange::new("name", json!("John"), json!("Jane"))]
+        );
+    }
+
+    #[test]
+    fn test_detect_changes_skips_rows_with_only_ignored_changes() {
+        let before_row = record(&[("id", json!(1)), ("updated_at", json!("10:00"))]);
+        let after_row = record(&[("id", json!(1)), ("updated_at", json!("11:00"))]);
+        let before = HashMap::from([("1".to_string(), &before_row)]);
+        let after = HashMap::from([("1".to_string(), &after_row)]);
+
+        let changes = detect_changes(&before, &after, |column| column == "updated_at");
+
+        assert!(!changes.has_changes());
+    }
+
+    #[test]
+    fn test_detect_changes_marks_ignored_columns() {
+        let before_row = record(&[("status", json!("pending")), ("updated_at", json!("10:00"))]);
+        let after_row = record(&[("status", json!("shipped")), ("updated_at", json!("11:00"))]);
+        let before = HashMap::from([("1".to_string(), &before_row)]);
+        let after = HashMap::from([("1".to_string(), &after_row)]);
+
+        let changes = detect_changes(&before, &after, |column| column == "updated_at");
+
+        assert_eq!(changes.modified_ids, vec!["1".to_string()]);
+        let columns = &changes.column_changes["1"];
+        assert_eq!(columns.len(), 2);
+        assert!(!columns[0].ignored);
+        assert!(columns[1].ignored);
+    }
+
+    #[test]
+    fn test_detect_multiset_changes_counts_duplicate_rows() {
+        let mut before = HashMap::new();
+        before.insert("a".to_string(), 3);
+        before.insert("b".to_string(), 1);
+
+        let mut after = HashMap::new();
+        after.insert("a".to_string(), 1);
+        after.insert("b".to_string(), 1);
+        after.insert("c".to_string(), 2);
+
+        let changes = detect_multiset_changes(&before, &after);
+
+        assert_eq!(changes.strategy, DiffStrategy::Multiset);
+        assert_eq!(changes.deleted_ids, vec!["a".to_string()]);
+        assert_eq!(changes.added_ids, vec!["c".to_string()]);
+        assert!(changes.modified_ids.is_empty());
+        assert_eq!(
+            changes.copy_counts.get("a"),
+            Some(&CopyCount {
+                before: 3,
+                after: 1
+            })
+        );
+        assert_eq!(
+            changes.copy_counts.get("c"),
+            Some(&CopyCount {
+                before: 0,
+                after: 2
+            })
+        );
+        assert!(!changes.copy_counts.contains_key("b"));
+    }
+
+    #[test]
+    fn test_detect_multiset_changes_no_changes() {
+        let mut before = HashMap::new();
+        before.insert("a".to_string(), 2);
+
+        let changes = detect_multiset_changes(&before, &before.clone());
+
+        assert!(!changes.has_changes());
+    }
+
+    #[test]
+    fn test_changed_columns() {
+        let before = record(&[
+            ("id", json!(1)),
+            ("status", json!("pending")),
+            ("note", json!("a")),
+        ]);
+        let after = record(&[
+            ("id", json!(1)),
+            ("status", json!("shipped")),
+            ("note", json!("a")),
+        ]);
+        assert_eq!(
+            changed_columns(&before, &after, |_| false),
+            vec![ColumnChange::new(
+                "status",
+                json!("pending"),
+                json!("shipped")
+            )]
+        );
+
+        // Added or removed columns count as changed
+        let after = record(&[("id", json!(1)), ("status", json!("pending"))]);
+        assert_eq!(
+            changed_columns(&before, &after, |_| false),
+            vec![ColumnChange::new(
+                "note",
+                json!("a"),
+                serde_json::Value::Null
+            )]
+        );
+    }
+
+    #[test]
+    fn test_copy_count_summary() {
+        assert_eq!(
+            CopyCount {
+                before: 3,
+                after: 1
+            }
+            .summary(),
+            "2 copies removed (3 → 1)"
+        );
+        assert_eq!(
+            CopyCount {
+                before: 0,
+                after: 1
+            }
+            .summary(),
+            "1 copy added (0 → 1)"
+        );
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("2", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("item10", "item9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1b", "a01b"), Ordering::Less);
+        assert_eq!(natural_cmp("abc", "abd"), Ordering::Less);
+        assert_eq!(natural_cmp("ab", "ab"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_sort_row_changes() {
+        let change = |id: serde_json::Value, kind| {
+            let row = Some(record(&[("id", id.clone())]));
+            RowChange {
+                key: row_key(row.as_ref().unwrap(), &["id".to_string()]),
+                kind,
+                before: row.clone().filter(|_| kind != ChangeKind::Added),
+                after: row.filter(|_| kind != ChangeKind::Deleted),
+                changed_columns: Vec::new(),
+                copy_count: None,
+                sequence: None,
+            }
+        };
+        let mut changes = vec![
+            change(json!(10), ChangeKind::Deleted),
+            change(json!(2), ChangeKind::Modified),
+            change(json!(-1), ChangeKind::Deleted),
+            change(json!(11), ChangeKind::Added),
+            change(json!(3), ChangeKind::Added),
+        ];
+        let keys = |changes: &[RowChange]| -> Vec<String> {
+            changes.iter().map(|change| change.key.clone()).collect()
+        };
+
+        sort_row_changes(&mut changes, &["id".to_string()], RowOrder::Kind);
+        assert_eq!(keys(&changes), vec!["3", "11", "2", "-1", "10"]);
+
+        sort_row_changes(&mut changes, &["id".to_string()], RowOrder::Key);
+        assert_eq!(keys(&changes), vec!["-1", "2", "3", "10", "11"]);
+    }
+
+    /// JSON report of changes to a keyed and a keyless table, spilling tables with
+    /// more than `threshold` rows
+    fn report_with_threshold(threshold: usize) -> serde_json::Value {
+        let mut output = Vec::new();
+        let mut report = DiffReport::new(JsonOutputAdapter::new(&mut output));
+        report.start().unwrap();
+
+        for (table_name, key_columns) in [("logs", vec![]), ("users", vec!["id".to_string()])] {
+            let identity = report.row_identity(table_name, &key_columns);
+            let mut before = SpillTable::new(key_columns.clone()).with_threshold(threshold);
+            let mut after = SpillTable::new(key_columns.clone()).with_threshold(threshold);
+            for id in 0..100 {
+                let row = record(&[("id", json!(id)), ("name", json!(format!("user{}", id)))]);
+                before.push(identity.of(&row), row).unwrap();
+            }
+            for id in 10..110 {
+                let name = if id % 7 == 0 {
+                    "renamed".to_string()
+                } else {
+                    format!("user{}", id)
+                };
+                let row = record(&[("id", json!(id)), ("name", json!(name))]);
+                after.push(identity.of(&row), row).unwrap();
+            }
+            before.finish().unwrap();
+            after.finish().unwrap();
+
+            report
+                .compare_rows(table_name, Some(&before), Some(&after))
+                .unwrap();
+        }
+
+        report.finish().unwrap();
+        drop(report);
+        serde_json::from_slice::<serde_json::Value>(&output).unwrap()["tables"].clone()
+    }
+
+    #[test]
+    fn test_spilled_tables_report_same_changes() {
+        let in_memory = report_with_threshold(usize::MAX);
+        let users = in_memory["users"]["rows"].as_object().unwrap();
+        assert_eq!(users["3"]["kind"], json!("deleted"));
+        assert_eq!(users["14"]["kind"], json!("modified"));
+        assert_eq!(users["105"]["kind"], json!("added"));
+
+        assert_eq!(report_with_threshold(10), in_memory);
+    }
+
+    #[test]
+    fn test_spilled_changes_are_written_in_order() {
+        let html_with_threshold = |threshold: usize| {
+            let mut output = Vec::new();
+            let mut report = DiffReport::new(HtmlOutputAdapter::new(&mut output));
+            let key_columns = vec!["id".to_string()];
+            let identity = report.row_identity("users", &key_columns);
+            let mut before = SpillTable::new(key_columns.clone()).with_threshold(threshold);
+            let mut after = SpillTable::new(key_columns.clone()).with_threshold(threshold);
+            for id in 0..100 {
+                let row = record(&[("id", json!(id)), ("name", json!("user"))]);
+                before.push(identity.of(&row), row).unwrap();
+                let row = record(&[("id", json!(id)), ("name", json!(format!("user{}", id)))]);
+                after.push(identity.of(&row), row).unwrap();
+            }
+            before.finish().unwrap();
+            after.finish().unwrap();
+            report
+                .compare_rows("users", Some(&before), Some(&after))
+                .unwrap();
+            drop(report);
+            String::from_utf8(output).unwrap()
+        };
+
+        let in_memory = html_with_threshold(usize::MAX);
+        let positions: Vec<_> = (0..100)
+            .map(|id| in_memory.find(&format!("user{}&quot;", id)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(html_with_threshold(10), in_memory);
+    }
+
+    #[test]
+    fn test_write_events_in_order_without_ignored_updates() {
+        let john = record(&[
+            ("id", json!(1)),
+            ("name", json!("John")),
+            ("updated_at", json!(1)),
+        ]);
+        let touched = record(&[
+            ("id", json!(1)),
+            ("name", json!("John")),
+            ("updated_at", json!(2)),
+        ]);
+        let jane = record(&[
+            ("id", json!(1)),
+            ("name", json!("Jane")),
+            ("updated_at", json!(3)),
+        ]);
+        let event = |sequence, kind, before: Option<&Row>, after: Option<&Row>| ChangeEvent {
+            sequence,
+            table_name: "users".to_string(),
+            kind,
+            before: before.cloned(),
+            after: after.cloned(),
+        };
+        let events = vec![
+            event(1, ChangeKind::Added, None, Some(&john)),
+            event(2, ChangeKind::Modified, Some(&john), Some(&touched)),
+            event(3, ChangeKind::Modified, Some(&touched), Some(&jane)),
+            event(4, ChangeKind::Deleted, Some(&jane), None),
+        ];
+
+        let mut output = Vec::new();
+        let options = DiffOptions {
+            ignored_columns: IgnoredColumns::new(&["updated_at".to_string()]).unwrap(),
+            ..DiffOptions::default()
+        };
+        let mut report = DiffReport::new(JsonOutputAdapter::new(&mut output)).with_options(options);
+        report.start().unwrap();
+        report
+            .write_events("users", TableStatus::Existing, &["id".to_string()], &events)
+            .unwrap();
+        assert!(report.finish().unwrap());
+        drop(report);
+
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let changes = report["tables"]["users"]["changes"].as_array().unwrap();
+        let sequences: Vec<_> = changes.iter().map(|c| c["sequence"].clone()).collect();
+        assert_eq!(sequences, vec![json!(1), json!(3), json!(4)]);
+        assert_eq!(changes[1]["key"], json!("1"));
+        assert_eq!(
+            changes[1]["changed_columns"],
+            json!([{"column": "name", "before": "John", "after": "Jane"}])
+        );
+        assert!(changes[0]["after"].get("updated_at").is_none());
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        let column = |name: &str, data_type: &str| ColumnDefinition {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: true,
+            default: None,
+        };
+        let mut before = TableSchema {
+            columns: vec![
+                column("id", "int"),
+                column("email", "text"),
+                column("age", "int"),
+            ],
+            ..TableSchema::default()
+        };
+        before.push_index_column("idx_email", false, false, "email".to_string());
+        before.push_foreign_key_column("fk_team", "team_id".to_string(), "teams".to_string(), None);
+        let mut after = TableSchema {
+            columns: vec![
+                column("id", "bigint"),
+                column("email", "text"),
+                column("name", "text"),
+            ],
+            ..TableSchema::default()
+        };
+        after.push_index_column("idx_email", false, true, "email".to_string());
+
+        let summaries: Vec<_> = schema_changes("users", Some(&before), Some(&after))
+            .iter()
+            .map(SchemaChange::summary)
+            .collect();
+
+        assert_eq!(
+            summaries,
+            vec![
+                "users.id: column altered (int → bigint)",
+                "users.age: column dropped (int)",
+                "users.name: column added (text)",
+                "users.idx_email: index altered ((email) → UNIQUE (email))",
+                "users.fk_team: foreign key dropped ((team_id) REFERENCES teams)",
+            ]
+        );
+        assert!(schema_changes("users", Some(&before), Some(&before)).is_empty());
+        assert_eq!(
+            schema_changes("users", Some(&before), None)[0].summary(),
+            "users: table dropped (id int, email text, age int)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_reports_changes_made_by_usecase() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'John');",
+        )
+        .await;
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(db.adapter().await, HtmlOutputAdapter::new(&mut output));
+        let (has_changes, outcome) = db_diff
+            .execute_with(|| async {
+                db.execute("UPDATE users SET name = 'Jane' WHERE id = 1")
+                    .await;
+                Ok(42)
+            })
+            .await
+            .unwrap();
+        drop(db_diff);
+
+        assert!(has_changes);
+        assert_eq!(outcome, 42);
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.contains("Jane"));
+        assert!(html.contains("name: John → Jane"));
+        assert!(!html.contains("No Changes Detected"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_no_changes() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'John');",
+        )
+        .await;
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(db.adapter().await, HtmlOutputAdapter::new(&mut output));
+        let (has_changes, ()) = db_diff.execute_with(|| async { Ok(()) }).await.unwrap();
+        drop(db_diff);
+
+        assert!(!has_changes);
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.contains("No Changes Detected"));
+        assert!(html.contains("TIMESTAMP values in UTC"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_labels_created_and_dropped_tables() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'John');",
+        )
+        .await;
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(db.adapter().await, JsonOutputAdapter::new(&mut output));
+        let (has_changes, ()) = db_diff
+            .execute_with(|| async {
+                db.execute("DROP TABLE users").await;
+                db.execute("CREATE TABLE teams (id INTEGER PRIMARY KEY)")
+                    .await;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        let changed_tables: Vec<_> = db_diff.changed_tables().iter().cloned().collect();
+        drop(db_diff);
+
+        assert!(has_changes);
+        assert_eq!(changed_tables, vec!["teams", "users"]);
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["tables"]["teams"]["status"], json!("created"));
+        assert_eq!(report["tables"]["users"]["status"], json!("dropped"));
+        assert_eq!(
+            report["tables"]["users"]["rows"]["1"]["kind"],
+            json!("deleted")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_triggers_reports_reverted_changes() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO users VALUES (1, 'John');",
+        )
+        .await;
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(db.adapter().await, JsonOutputAdapter::new(&mut output))
+            .with_mode(CaptureMode::Triggers);
+        let (has_changes, ()) = db_diff
+            .execute_with(|| async {
+                db.execute("UPDATE users SET name = 'Jane' WHERE id = 1")
+                    .await;
+                db.execute("UPDATE users SET name = 'John' WHERE id = 1")
+                    .await;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        drop(db_diff);
+
+        assert!(has_changes);
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let changes = report["tables"]["users"]["changes"].as_array().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0]["after"]["name"], json!("Jane"));
+        assert_eq!(changes[1]["after"]["name"], json!("John"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_triggers_cleans_up_after_failed_usecase() {
+        let db =
+            TestDatabase::create("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);").await;
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(db.adapter().await, JsonOutputAdapter::new(&mut output))
+            .with_mode(CaptureMode::Triggers);
+        let result: Result<(bool, ())> = db_diff
+            .execute_with(|| async { Err(DbbaError::Unknown("usecase failed".to_string())) })
+            .await;
+        drop(db_diff);
+
+        assert!(result.is_err());
+        let tables = db.adapter().await.list_tables().await.unwrap();
+        assert_eq!(tables, vec!["users"]);
+    }
+
+    /// SQLite adapter with a content checksum, recording which tables are scanned
+    struct ChecksumAdapter {
+        inner: crate::adapters::SqliteAdapter,
+        scanned: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl DatabaseAdapter for ChecksumAdapter {
+        async fn connect(&mut self) -> Result<()> {
+            self.inner.connect().await
+        }
+
+        async fn disconnect(&mut self) -> Result<()> {
+            self.inner.disconnect().await
+        }
+
+        async fn key_columns(&self, table_name: &str) -> Result<Vec<String>> {
+            self.inner.key_columns(table_name).await
+        }
+
+        async fn scan_table(
+            &self,
+            table_name: &str,
+            sink: &mut crate::adapters::database::RowSink<'_>,
+        ) -> Result<()> {
+            self.scanned.lock().unwrap().push(table_name.to_string());
+            self.inner.scan_table(table_name, sink).await
+        }
+
+        async fn table_checksum(&self, table_name: &str) -> Result<Option<TableChecksum>> {
+            let table = self.inner.read_table(table_name).await?;
+            Ok(Some(TableChecksum {
+                row_count: Some(table.rows.len() as i64),
+                checksum: serde_json::to_string(&table.rows)?,
+            }))
+        }
+
+        async fn list_tables(&self) -> Result<Vec<String>> {
+            self.inner.list_tables().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_skips_tables_with_unchanged_checksum() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE logs (message TEXT);
+             INSERT INTO users VALUES (1, 'John');
+             INSERT INTO logs VALUES ('hello');",
+        )
+        .await;
+        let adapter = ChecksumAdapter {
+            inner: db.adapter().await,
+            scanned: Default::default(),
+        };
+        let mut output = Vec::new();
+
+        let mut db_diff = DbDiff::new(adapter, HtmlOutputAdapter::new(&mut output)).with_jobs(2);
+        let (has_changes, ()) = db_diff
+            .execute_with(|| async {
+                db.execute("UPDATE users SET name = 'Jane' WHERE id = 1")
+                    .await;
+                Ok(())
+            })
+            .await
+            .unwrap();
+        let mut scanned = db_diff.db_adapter.scanned.lock().unwrap().clone();
+        drop(db_diff);
+
+        assert!(has_changes);
+        scanned.sort();
+        assert_eq!(scanned, vec!["logs", "users", "users"]);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("name: John → Jane"));
     }
 }
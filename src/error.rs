@@ -16,6 +16,18 @@ pub enum DbbaError {
     #[error("Clipboard error: {0}")]
     Clipboard(#[from] arboard::Error),
 
+    #[error("Binlog error: {0}")]
+    Binlog(String),
+
+    #[error("Snapshot file error: {0}")]
+    Snapshot(String),
+
+    #[error("Decode error: {0}")]
+    Decode(String),
+
+    #[error("Interrupted")]
+    Interrupted,
+
     #[error("Configuration error: {0}")]
     Config(String),
 
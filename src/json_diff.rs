@@ -0,0 +1,211 @@
+//! Structural comparison of JSON values.
+//!
+//! Objects are compared key by key and arrays element by element, so a change deep
+//! inside a JSON column is reported at its JSON Pointer path instead of as a whole new
+//! value. Array elements are aligned with a diff of their contents: an element removed
+//! in one place and added unchanged in another is reported as moved.
+
+use crate::models::{JsonChange, JsonChangeKind};
+use serde_json::{Map, Value};
+use similar::{Algorithm, DiffTag};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Changes from `before` to `after`, in document order
+pub fn json_changes(before: &Value, after: &Value) -> Vec<JsonChange> {
+    let mut changes = Vec::new();
+    diff_values("", before, after, &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<JsonChange>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => diff_objects(path, before, after, changes),
+        (Value::Array(before), Value::Array(after)) => diff_arrays(path, before, after, changes),
+        _ if before != after => changes.push(change(
+            JsonChangeKind::Changed,
+            path.to_string(),
+            Some(before),
+            Some(after),
+        )),
+        _ => {}
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+    changes: &mut Vec<JsonChange>,
+) {
+    let keys: std::collections::BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let key_path = pointer(path, key);
+        match (before.get(key), after.get(key)) {
+            (Some(before), Some(after)) => diff_values(&key_path, before, after, changes),
+            (Some(before), None) => changes.push(change(
+                JsonChangeKind::Removed,
+                key_path,
+                Some(before),
+                None,
+            )),
+            (None, Some(after)) => {
+                changes.push(change(JsonChangeKind::Added, key_path, None, Some(after)))
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_arrays(path: &str, before: &[Value], after: &[Value], changes: &mut Vec<JsonChange>) {
+    let encode = |values: &[Value]| values.iter().map(Value::to_string).collect::<Vec<_>>();
+    let hunks: Vec<(Range<usize>, Range<usize>)> =
+        similar::capture_diff_slices(Algorithm::Myers, &encode(before), &encode(after))
+            .iter()
+            .map(|op| op.as_tag_tuple())
+            .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+            .map(|(_, old, new)| (old, new))
+            .collect();
+
+    // An element removed in one place and added unchanged in another was moved
+    let mut moved_from: HashMap<usize, usize> = HashMap::new();
+    let mut moved: HashSet<usize> = HashSet::new();
+    for (old, _) in &hunks {
+        for old_index in old.clone() {
+            let target = hunks
+                .iter()
+                .flat_map(|(_, new)| new.clone())
+                .find(|new_index| {
+                    !moved_from.contains_key(new_index) && before[old_index] == after[*new_index]
+                });
+            if let Some(new_index) = target {
+                moved_from.insert(new_index, old_index);
+                moved.insert(old_index);
+            }
+        }
+    }
+
+    // Within a hunk, the remaining elements replace each other in order
+    for (old, new) in hunks {
+        let removed: Vec<usize> = old.filter(|index| !moved.contains(index)).collect();
+        let mut added = Vec::new();
+        for new_index in new {
+            match moved_from.get(&new_index) {
+                Some(&old_index) => changes.push(JsonChange {
+                    from: Some(pointer(path, &old_index.to_string())),
+                    ..change(
+                        JsonChangeKind::Moved,
+                        pointer(path, &new_index.to_string()),
+                        None,
+                        None,
+                    )
+                }),
+                None => added.push(new_index),
+            }
+        }
+
+        let replaced = removed.len().min(added.len());
+        for (&old_index, &new_index) in removed.iter().zip(&added) {
+            let element_path = pointer(path, &new_index.to_string());
+            diff_values(
+                &element_path,
+                &before[old_index],
+                &after[new_index],
+                changes,
+            );
+        }
+        for &old_index in &removed[replaced..] {
+            let element_path = pointer(path, &old_index.to_string());
+            changes.push(change(
+                JsonChangeKind::Removed,
+                element_path,
+                Some(&before[old_index]),
+                None,
+            ));
+        }
+        for &new_index in &added[replaced..] {
+            let element_path = pointer(path, &new_index.to_string());
+            changes.push(change(
+                JsonChangeKind::Added,
+                element_path,
+                None,
+                Some(&after[new_index]),
+            ));
+        }
+    }
+}
+
+fn change(
+    kind: JsonChangeKind,
+    path: String,
+    before: Option<&Value>,
+    after: Option<&Value>,
+) -> JsonChange {
+    JsonChange {
+        path,
+        kind,
+        from: None,
+        before: before.cloned(),
+        after: after.cloned(),
+    }
+}
+
+/// Append a key or index to a JSON Pointer, escaping `~` and `/`
+fn pointer(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn summaries(before: Value, after: Value) -> Vec<String> {
+        json_changes(&before, &after)
+            .iter()
+            .map(JsonChange::summary)
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_object_changes() {
+        let before = json!({"theme": "dark", "notify": {"email": true, "sms": false}, "old": 1});
+        let after = json!({"theme": "light", "notify": {"email": true, "sms": true}, "new/key": 2});
+
+        assert_eq!(
+            summaries(before, after),
+            vec![
+                "/new~1key: added 2",
+                "/notify/sms: false → true",
+                "/old: removed 1",
+                "/theme: \"dark\" → \"light\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_array_changes() {
+        assert_eq!(
+            summaries(json!(["a", "b", "c"]), json!(["a", "c", "d"])),
+            vec!["/1: removed \"b\"", "/2: added \"d\""]
+        );
+        assert_eq!(
+            summaries(json!([{"id": 1, "qty": 1}]), json!([{"id": 1, "qty": 2}])),
+            vec!["/0/qty: 1 → 2"]
+        );
+    }
+
+    #[test]
+    fn test_moved_array_elements() {
+        let changes = json_changes(&json!(["a", "b", "c"]), &json!(["c", "a", "b"]));
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, JsonChangeKind::Moved);
+        assert_eq!(changes[0].summary(), "/0: moved from /2");
+    }
+
+    #[test]
+    fn test_equal_values() {
+        assert!(json_changes(&json!({"a": [1, 2]}), &json!({"a": [1, 2]})).is_empty());
+    }
+}
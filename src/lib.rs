@@ -1,8 +1,11 @@
 pub mod adapters;
 pub mod db_diff;
 pub mod error;
+pub mod json_diff;
 pub mod models;
+pub mod snapshot;
+pub mod spill;
 
-pub use db_diff::DbDiff;
+pub use db_diff::{DbDiff, DiffReport};
 pub use error::{DbbaError, Result};
-pub use models::{DbInfo, TableData};
+pub use models::{DbInfo, Row, TableData, TableSnapshot};
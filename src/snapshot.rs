@@ -0,0 +1,937 @@
+//! Snapshot files (`.dbsnap`) for comparing databases captured at different times.
+//!
+//! A snapshot is a JSON Lines file: a header record followed by each table's metadata
+//! record and its rows, with tables in ascending name order, and an end record. Rows
+//! are streamed while writing and reading, so neither snapshot is ever loaded into
+//! memory as a whole; large tables are spilled to disk while being compared.
+
+use crate::adapters::database::begin_consistent_snapshot;
+use crate::adapters::{DatabaseAdapter, OutputAdapter};
+use crate::db_diff::{row_key, DiffReport, RowIdentity};
+use crate::error::{DbbaError, Result};
+use crate::models::{
+    BinaryFormat, BinaryFormats, DbInfo, Driver, Row, TableFilter, TableSchema, TableSnapshot,
+    Timezone,
+};
+use crate::spill::SpillTable;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+/// Identifies a dbba snapshot file
+pub const SNAPSHOT_FORMAT: &str = "dbba-snapshot";
+
+/// Current snapshot format version; readers reject newer versions.
+///
+/// Version 1 stored each table's row count up front; version 2 streams rows and ends
+/// with an end record instead.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// First record of a snapshot file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub format: String,
+    pub version: u32,
+    /// Capture start time (RFC 3339)
+    pub captured_at: String,
+    pub driver: Driver,
+    pub database: String,
+    /// Format the values of binary columns were rendered in
+    #[serde(default)]
+    pub binary_format: BinaryFormat,
+    /// `PATTERN=FORMAT` rules overriding `binary_format` for some columns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binary_columns: Vec<String>,
+    /// Time zone TIMESTAMP values were read in (UTC in snapshots that don't record one)
+    #[serde(default)]
+    pub timezone: Timezone,
+}
+
+impl SnapshotHeader {
+    /// Header for a snapshot of the given database captured now
+    pub fn new(db_info: &DbInfo) -> Self {
+        Self {
+            format: SNAPSHOT_FORMAT.to_string(),
+            version: SNAPSHOT_VERSION,
+            captured_at: chrono::Local::now().to_rfc3339(),
+            driver: db_info.driver,
+            database: db_info.database_name(),
+            binary_format: BinaryFormat::default(),
+            binary_columns: Vec::new(),
+            timezone: Timezone::default(),
+        }
+    }
+
+    /// Record the time zone TIMESTAMP values of the snapshot are read in
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Record the binary formats the values of the snapshot are rendered in
+    pub fn with_binary_formats(mut self, binary_formats: &BinaryFormats) -> Self {
+        self.binary_format = binary_formats.default_format();
+        self.binary_columns = binary_formats.rules();
+        self
+    }
+}
+
+/// Metadata record written before a table's rows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableMetadata {
+    pub name: String,
+    pub key_columns: Vec<String>,
+    /// Number of rows that follow (version 1 only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_count: Option<usize>,
+    /// Definition of the table, if the database adapter reads table definitions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<TableSchema>,
+}
+
+/// Last record of a snapshot file, telling a complete snapshot from a truncated one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEnd {
+    pub table_count: usize,
+}
+
+/// A single line of a snapshot file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotRecord {
+    Header(SnapshotHeader),
+    Table(TableMetadata),
+    Row(BTreeMap<String, Value>),
+    End(SnapshotEnd),
+}
+
+/// Writes a snapshot file table by table
+pub struct SnapshotWriter<W: Write> {
+    writer: W,
+    last_table: Option<String>,
+    table_count: usize,
+    jobs: usize,
+    consistent_snapshot: bool,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    /// Start a snapshot file by writing its header
+    pub fn new(writer: W, header: &SnapshotHeader) -> Result<Self> {
+        let mut snapshot = Self {
+            writer,
+            last_table: None,
+            table_count: 0,
+            jobs: 1,
+            consistent_snapshot: true,
+        };
+        snapshot.write_record(&SnapshotRecord::Header(header.clone()))?;
+        Ok(snapshot)
+    }
+
+    /// Set the number of tables `write_database` reads concurrently
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Set whether `write_database` reads all tables from one consistent state of the
+    /// database (default: true)
+    pub fn with_consistent_snapshot(mut self, consistent_snapshot: bool) -> Self {
+        self.consistent_snapshot = consistent_snapshot;
+        self
+    }
+
+    /// Append a table; tables must be written in ascending name order
+    pub fn write_table(&mut self, table_name: &str, table: &TableSnapshot) -> Result<()> {
+        self.start_table(table_name, &table.key_columns, table.schema.as_ref())?;
+        for row in &table.rows {
+            self.write_row(row)?;
+        }
+        Ok(())
+    }
+
+    /// Start a table whose rows are then appended with `write_row`
+    pub fn start_table(
+        &mut self,
+        table_name: &str,
+        key_columns: &[String],
+        schema: Option<&TableSchema>,
+    ) -> Result<()> {
+        if let Some(last_table) = &self.last_table {
+            if last_table.as_str() >= table_name {
+                return Err(DbbaError::Snapshot(format!(
+                    "table '{}' written after '{}'; tables must be in ascending order",
+                    table_name, last_table
+                )));
+            }
+        }
+
+        self.write_record(&SnapshotRecord::Table(TableMetadata {
+            name: table_name.to_string(),
+            key_columns: key_columns.to_vec(),
+            row_count: None,
+            schema: schema.cloned(),
+        }))?;
+
+        self.last_table = Some(table_name.to_string());
+        self.table_count += 1;
+        Ok(())
+    }
+
+    /// Append a row to the current table
+    pub fn write_row(&mut self, row: &Row) -> Result<()> {
+        if self.last_table.is_none() {
+            return Err(DbbaError::Snapshot(
+                "row written before any table".to_string(),
+            ));
+        }
+        serde_json::to_writer(&mut self.writer, &SnapshotRowRef::Row(row))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Stream the tables selected by `filter` into the snapshot, one row at a time,
+    /// from one consistent snapshot of the database unless disabled
+    pub async fn write_database<D: DatabaseAdapter + ?Sized>(
+        &mut self,
+        db_adapter: &D,
+        filter: &TableFilter,
+    ) -> Result<()>
+    where
+        W: Send,
+    {
+        if !self.consistent_snapshot {
+            return self.write_tables(db_adapter, filter, self.jobs).await;
+        }
+
+        let jobs = begin_consistent_snapshot(db_adapter, self.jobs).await?;
+        let written = self.write_tables(db_adapter, filter, jobs).await;
+        db_adapter.end_snapshot().await?;
+        written
+    }
+
+    /// Write the tables selected by `filter`, reading up to `jobs` tables at a time.
+    ///
+    /// With more than one job, tables are read concurrently into spillable row storage
+    /// and written in name order as they complete.
+    async fn write_tables<D: DatabaseAdapter + ?Sized>(
+        &mut self,
+        db_adapter: &D,
+        filter: &TableFilter,
+        jobs: usize,
+    ) -> Result<()>
+    where
+        W: Send,
+    {
+        let mut table_names = db_adapter.list_tables().await?;
+        table_names.retain(|table_name| filter.matches(table_name));
+        table_names.sort();
+
+        if jobs == 1 {
+            for table_name in table_names {
+                let key_columns = db_adapter.key_columns(&table_name).await?;
+                let schema = db_adapter.table_schema(&table_name).await?;
+                self.start_table(&table_name, &key_columns, schema.as_ref())?;
+                db_adapter
+                    .scan_table(&table_name, &mut |row| self.write_row(&row))
+                    .await?;
+            }
+            return Ok(());
+        }
+
+        let mut tables = stream::iter(table_names)
+            .map(|table_name| read_table_rows(db_adapter, table_name))
+            .buffered(jobs);
+        while let Some((table_name, rows, schema)) = tables.try_next().await? {
+            self.start_table(&table_name, rows.key_columns(), schema.as_ref())?;
+            let partition_count = rows.partition_count();
+            for index in 0..partition_count {
+                for (_, row) in rows.partition(index, partition_count)?.iter() {
+                    self.write_row(row)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the end record, flush the snapshot and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.write_record(&SnapshotRecord::End(SnapshotEnd {
+            table_count: self.table_count,
+        }))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_record(&mut self, record: &SnapshotRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Read a table and its definition into spillable row storage, identifying rows by
+/// their key
+async fn read_table_rows<D: DatabaseAdapter + ?Sized>(
+    db_adapter: &D,
+    table_name: String,
+) -> Result<(String, SpillTable, Option<TableSchema>)> {
+    let key_columns = db_adapter.key_columns(&table_name).await?;
+    let schema = db_adapter.table_schema(&table_name).await?;
+    let mut rows = SpillTable::new(key_columns.clone());
+    db_adapter
+        .scan_table(&table_name, &mut |row| {
+            rows.push(row_key(&row, &key_columns), row)
+        })
+        .await?;
+    rows.finish()?;
+    Ok((table_name, rows, schema))
+}
+
+/// Borrowing counterpart of `SnapshotRecord::Row`, so rows are not cloned when written
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SnapshotRowRef<'a> {
+    Row(&'a BTreeMap<String, Value>),
+}
+
+/// Reads a snapshot file table by table, streaming each table's rows
+pub struct SnapshotReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    header: SnapshotHeader,
+    line_number: usize,
+    last_table: Option<String>,
+    /// Record read past the rows of the current table
+    pending: Option<SnapshotRecord>,
+    /// Whether rows of the current table remain to be read
+    in_table: bool,
+    /// Rows of the current table left to read (version 1 only)
+    remaining_rows: Option<usize>,
+}
+
+impl<R: BufRead> SnapshotReader<R> {
+    /// Open a snapshot, checking its header
+    pub fn new(reader: R) -> Result<Self> {
+        let mut lines = reader.lines();
+        let record = match lines.next() {
+            Some(line) => line.ok().and_then(|line| serde_json::from_str(&line).ok()),
+            None => None,
+        };
+        let header = match record {
+            Some(SnapshotRecord::Header(header)) if header.format == SNAPSHOT_FORMAT => header,
+            _ => {
+                return Err(DbbaError::Snapshot(
+                    "not a dbba snapshot (missing header)".to_string(),
+                ))
+            }
+        };
+        if header.version > SNAPSHOT_VERSION {
+            return Err(DbbaError::Snapshot(format!(
+                "snapshot version {} is not supported (supported up to {})",
+                header.version, SNAPSHOT_VERSION
+            )));
+        }
+
+        Ok(Self {
+            lines,
+            header,
+            line_number: 1,
+            last_table: None,
+            pending: None,
+            in_table: false,
+            remaining_rows: None,
+        })
+    }
+
+    /// Header of the snapshot
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Read the next table, or `None` at the end of the snapshot
+    pub fn next_table(&mut self) -> Result<Option<(String, TableSnapshot)>> {
+        let Some(metadata) = self.next_table_metadata()? else {
+            return Ok(None);
+        };
+
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_row()? {
+            rows.push(row);
+        }
+
+        Ok(Some((
+            metadata.name,
+            TableSnapshot {
+                key_columns: metadata.key_columns,
+                rows,
+                schema: metadata.schema,
+            },
+        )))
+    }
+
+    /// Move to the next table, skipping any unread rows of the current one.
+    ///
+    /// Returns `None` at the end of the snapshot; its rows are then read with `next_row`.
+    pub fn next_table_metadata(&mut self) -> Result<Option<TableMetadata>> {
+        while self.next_row()?.is_some() {}
+
+        let record = match self.pending.take() {
+            Some(record) => Some(record),
+            None => self.next_record()?,
+        };
+        let metadata = match record {
+            Some(SnapshotRecord::Table(metadata)) => metadata,
+            Some(SnapshotRecord::End(_)) => return Ok(None),
+            None if self.header.version < 2 => return Ok(None),
+            None => return Err(self.error("snapshot is truncated (missing end record)")),
+            Some(_) => return Err(self.error("expected a table record")),
+        };
+        if let Some(last_table) = &self.last_table {
+            if last_table >= &metadata.name {
+                return Err(self.error("tables are not in ascending order"));
+            }
+        }
+
+        self.last_table = Some(metadata.name.clone());
+        self.in_table = true;
+        self.remaining_rows = metadata.row_count;
+        Ok(Some(metadata))
+    }
+
+    /// Read the next row of the current table, or `None` after its last row
+    pub fn next_row(&mut self) -> Result<Option<Row>> {
+        if !self.in_table {
+            return Ok(None);
+        }
+
+        if let Some(remaining_rows) = self.remaining_rows {
+            if remaining_rows == 0 {
+                self.in_table = false;
+                return Ok(None);
+            }
+            return match self.next_record()? {
+                Some(SnapshotRecord::Row(row)) => {
+                    self.remaining_rows = Some(remaining_rows - 1);
+                    Ok(Some(row))
+                }
+                _ => Err(self.error(&format!(
+                    "table '{}' has fewer rows than its row_count",
+                    self.last_table.as_deref().unwrap_or_default()
+                ))),
+            };
+        }
+
+        match self.next_record()? {
+            Some(SnapshotRecord::Row(row)) => Ok(Some(row)),
+            Some(record) => {
+                self.pending = Some(record);
+                self.in_table = false;
+                Ok(None)
+            }
+            None => Err(self.error("snapshot is truncated (missing end record)")),
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<SnapshotRecord>> {
+        let Some(line) = self.lines.next() else {
+            return Ok(None);
+        };
+        let line = line?;
+        self.line_number += 1;
+        serde_json::from_str(&line)
+            .map(Some)
+            .map_err(|e| self.error(&e.to_string()))
+    }
+
+    fn error(&self, message: &str) -> DbbaError {
+        DbbaError::Snapshot(format!("line {}: {}", self.line_number, message))
+    }
+}
+
+/// Compare two snapshot files table by table and write the changes to the report.
+///
+/// Like a live comparison, a table missing from one snapshot was created or dropped and
+/// is compared as empty there. Table definitions are compared when the snapshots record
+/// them.
+pub fn diff_snapshots<B: BufRead, A: BufRead, O: OutputAdapter>(
+    before: &mut SnapshotReader<B>,
+    after: &mut SnapshotReader<A>,
+    report: &mut DiffReport<O>,
+) -> Result<bool> {
+    // Otherwise every binary value would show up as changed
+    let (before_header, after_header) = (before.header(), after.header());
+    if before_header.binary_format != after_header.binary_format
+        || before_header.binary_columns != after_header.binary_columns
+    {
+        return Err(DbbaError::Snapshot(
+            "the snapshots render binary columns in different formats; take both with the same --binary-format and --binary-column options".to_string(),
+        ));
+    }
+    if before_header.timezone != after_header.timezone {
+        return Err(DbbaError::Snapshot(
+            "the snapshots read TIMESTAMP values in different time zones; take both with the same --timezone".to_string(),
+        ));
+    }
+
+    report.start()?;
+
+    let mut after_table = after.next_table_metadata()?;
+    while let Some(before_table) = before.next_table_metadata()? {
+        // Both files are sorted by table name, so skip after tables until we catch up
+        while let Some(table) = after_table
+            .as_ref()
+            .filter(|table| table.name < before_table.name)
+        {
+            compare_created_table(after, table, report)?;
+            after_table = after.next_table_metadata()?;
+        }
+
+        let table_name = &before_table.name;
+        match &after_table {
+            Some(table) if table.name == *table_name => {
+                // Snapshots by older versions don't record definitions
+                if let (Some(before_schema), Some(after_schema)) =
+                    (&before_table.schema, &table.schema)
+                {
+                    report.compare_schema(table_name, Some(before_schema), Some(after_schema));
+                }
+            }
+            _ => report.compare_schema(table_name, before_table.schema.as_ref(), None),
+        }
+
+        // Rows of both snapshots are identified by the key columns they had before
+        let identity = report.row_identity(table_name, &before_table.key_columns);
+        let before_rows = read_rows(before, &before_table, &identity)?;
+        let after_rows = match &after_table {
+            Some(table) if table.name == *table_name => {
+                Some(read_rows(after, &before_table, &identity)?)
+            }
+            _ => None,
+        };
+        report.compare_rows(table_name, Some(&before_rows), after_rows.as_ref())?;
+        if after_rows.is_some() {
+            after_table = after.next_table_metadata()?;
+        }
+    }
+
+    // Tables only in the after snapshot
+    while let Some(table) = after_table {
+        compare_created_table(after, &table, report)?;
+        after_table = after.next_table_metadata()?;
+    }
+
+    report.finish()
+}
+
+/// Report the current table of the after snapshot, which the before snapshot lacks
+fn compare_created_table<R: BufRead, O: OutputAdapter>(
+    after: &mut SnapshotReader<R>,
+    table: &TableMetadata,
+    report: &mut DiffReport<O>,
+) -> Result<()> {
+    report.compare_schema(&table.name, None, table.schema.as_ref());
+    let identity = report.row_identity(&table.name, &table.key_columns);
+    let after_rows = read_rows(after, table, &identity)?;
+    report.compare_rows(&table.name, None, Some(&after_rows))
+}
+
+/// Stream the rows of the current table into spillable row storage
+fn read_rows<R: BufRead>(
+    reader: &mut SnapshotReader<R>,
+    table: &TableMetadata,
+    identity: &RowIdentity,
+) -> Result<SpillTable> {
+    let mut rows = SpillTable::new(table.key_columns.clone());
+    while let Some(row) = reader.next_row()? {
+        rows.push(identity.of(&row), row)?;
+    }
+    rows.finish()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::sqlite::tests::TestDatabase;
+    use crate::adapters::{HtmlOutputAdapter, JsonOutputAdapter};
+    use crate::models::SchemaChange;
+    use serde_json::json;
+
+    fn header() -> SnapshotHeader {
+        SnapshotHeader {
+            format: SNAPSHOT_FORMAT.to_string(),
+            version: SNAPSHOT_VERSION,
+            captured_at: "2024-01-01T00:00:00+00:00".to_string(),
+            driver: Driver::Sqlite,
+            database: "app.db".to_string(),
+            binary_format: BinaryFormat::default(),
+            binary_columns: Vec::new(),
+            timezone: Timezone::default(),
+        }
+    }
+
+    fn users() -> TableSnapshot {
+        TableSnapshot {
+            key_columns: vec!["id".to_string()],
+            rows: vec![
+                [
+                    ("id".to_string(), json!(1)),
+                    ("name".to_string(), json!("John")),
+                ]
+                .into_iter()
+                .collect(),
+                [
+                    ("id".to_string(), json!(2)),
+                    ("name".to_string(), json!("Jane")),
+                ]
+                .into_iter()
+                .collect(),
+            ],
+            schema: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer
+            .write_table("logs", &TableSnapshot::default())
+            .unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = SnapshotReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.header(), &header());
+
+        let (name, logs) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "logs");
+        assert!(logs.rows.is_empty());
+
+        let (name, table) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "users");
+        assert_eq!(table.key_columns, users().key_columns);
+        assert_eq!(table.rows, users().rows);
+
+        assert!(reader.next_table().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_one_record_per_line() {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with(r#"{"header":{"format":"dbba-snapshot","version":2,"#));
+        assert_eq!(
+            lines[1],
+            r#"{"table":{"name":"users","key_columns":["id"]}}"#
+        );
+        assert_eq!(lines[2], r#"{"row":{"id":1,"name":"John"}}"#);
+        assert_eq!(lines[4], r#"{"end":{"table_count":1}}"#);
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let text = [
+            r#"{"header":{"format":"dbba-snapshot","version":1,"captured_at":"2024-01-01T00:00:00+00:00","driver":"sqlite","database":"app.db"}}"#,
+            r#"{"table":{"name":"logs","key_columns":[],"row_count":0}}"#,
+            r#"{"table":{"name":"users","key_columns":["id"],"row_count":2}}"#,
+            r#"{"row":{"id":1,"name":"John"}}"#,
+            r#"{"row":{"id":2,"name":"Jane"}}"#,
+        ]
+        .join("\n");
+
+        let mut reader = SnapshotReader::new(text.as_bytes()).unwrap();
+        let (name, logs) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "logs");
+        assert!(logs.rows.is_empty());
+        let (name, table) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "users");
+        assert_eq!(table.rows, users().rows);
+        assert!(reader.next_table().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_skips_unread_rows() {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer.write_table("accounts", &users()).unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = SnapshotReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(
+            reader.next_table_metadata().unwrap().unwrap().name,
+            "accounts"
+        );
+        assert_eq!(reader.next_row().unwrap(), Some(users().rows[0].clone()));
+
+        let table = reader.next_table_metadata().unwrap().unwrap();
+        assert_eq!(table.name, "users");
+        assert_eq!(table.key_columns, vec!["id"]);
+        assert_eq!(reader.next_row().unwrap(), Some(users().rows[0].clone()));
+        assert!(reader.next_table_metadata().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_writer_rejects_unsorted_tables() {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer.write_table("users", &users()).unwrap();
+
+        assert!(writer.write_table("logs", &users()).is_err());
+    }
+
+    #[test]
+    fn test_reader_rejects_other_files() {
+        assert!(SnapshotReader::new(&b"{\"id\":1}\n"[..]).is_err());
+        assert!(SnapshotReader::new(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_reader_rejects_newer_version() {
+        let mut newer = header();
+        newer.version = SNAPSHOT_VERSION + 1;
+        let bytes = SnapshotWriter::new(Vec::new(), &newer)
+            .unwrap()
+            .finish()
+            .unwrap();
+
+        let error = SnapshotReader::new(bytes.as_slice()).err().unwrap();
+        assert!(error.to_string().contains("not supported"));
+    }
+
+    #[test]
+    fn test_reader_rejects_truncated_table() {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let truncated: String = text.lines().take(3).map(|l| format!("{}\n", l)).collect();
+
+        let mut reader = SnapshotReader::new(truncated.as_bytes()).unwrap();
+        assert!(reader.next_table().is_err());
+
+        // A complete table without the end record is still an interrupted snapshot
+        let truncated: String = text.lines().take(4).map(|l| format!("{}\n", l)).collect();
+        let mut reader = SnapshotReader::new(truncated.as_bytes()).unwrap();
+        assert!(reader.next_table().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_database_from_adapter() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE logs (message TEXT);
+             INSERT INTO users VALUES (1, 'John');
+             INSERT INTO logs VALUES ('hello');",
+        )
+        .await;
+
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        writer
+            .write_database(&db.adapter().await, &TableFilter::default())
+            .await
+            .unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = SnapshotReader::new(bytes.as_slice()).unwrap();
+        let (name, logs) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "logs");
+        assert_eq!(logs.key_columns, vec!["rowid"]);
+        let (name, users) = reader.next_table().unwrap().unwrap();
+        assert_eq!(name, "users");
+        assert_eq!(users.rows[0]["name"], json!("John"));
+        assert!(reader.next_table().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_database_with_jobs_matches_sequential() {
+        let db = TestDatabase::create(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE logs (message TEXT);
+             CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER);
+             INSERT INTO users VALUES (1, 'John'), (2, 'Jane');
+             INSERT INTO logs VALUES ('hello'), ('hello');
+             INSERT INTO orders VALUES (10, 1);",
+        )
+        .await;
+        let adapter = db.adapter().await;
+
+        let mut snapshots = Vec::new();
+        for jobs in [1, 3] {
+            let mut writer = SnapshotWriter::new(Vec::new(), &header())
+                .unwrap()
+                .with_jobs(jobs);
+            writer
+                .write_database(&adapter, &TableFilter::default())
+                .await
+                .unwrap();
+            snapshots.push(writer.finish().unwrap());
+        }
+
+        assert_eq!(snapshots[0], snapshots[1]);
+    }
+
+    fn snapshot(tables: &[(&str, TableSnapshot)]) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+        for (name, table) in tables {
+            writer.write_table(name, table).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_diff_snapshots_matches_tables_by_name() {
+        let mut changed = users();
+        changed.rows[1].insert("name".to_string(), json!("Janet"));
+        let before = snapshot(&[("logs", TableSnapshot::default()), ("users", users())]);
+        let after = snapshot(&[("accounts", TableSnapshot::default()), ("users", changed)]);
+        let mut output = Vec::new();
+
+        let has_changes = diff_snapshots(
+            &mut SnapshotReader::new(before.as_slice()).unwrap(),
+            &mut SnapshotReader::new(after.as_slice()).unwrap(),
+            &mut DiffReport::new(HtmlOutputAdapter::new(&mut output)),
+        )
+        .unwrap();
+
+        assert!(has_changes);
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.contains("Janet"));
+        assert!(!html.contains("John"));
+    }
+
+    #[test]
+    fn test_diff_snapshots_labels_created_and_dropped_tables() {
+        let before = snapshot(&[("logs", TableSnapshot::default())]);
+        let after = snapshot(&[("accounts", TableSnapshot::default()), ("users", users())]);
+        let mut output = Vec::new();
+
+        let has_changes = diff_snapshots(
+            &mut SnapshotReader::new(before.as_slice()).unwrap(),
+            &mut SnapshotReader::new(after.as_slice()).unwrap(),
+            &mut DiffReport::new(JsonOutputAdapter::new(&mut output)),
+        )
+        .unwrap();
+
+        assert!(has_changes);
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(report["tables"]["accounts"]["status"], json!("created"));
+        assert_eq!(report["tables"]["logs"]["status"], json!("dropped"));
+        assert_eq!(report["tables"]["users"]["status"], json!("created"));
+        assert_eq!(
+            report["tables"]["users"]["rows"].as_object().unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_diff_snapshots_no_changes() {
+        let before = snapshot(&[("users", users())]);
+        let after = snapshot(&[("users", users())]);
+        let mut output = Vec::new();
+
+        let has_changes = diff_snapshots(
+            &mut SnapshotReader::new(before.as_slice()).unwrap(),
+            &mut SnapshotReader::new(after.as_slice()).unwrap(),
+            &mut DiffReport::new(HtmlOutputAdapter::new(&mut output)),
+        )
+        .unwrap();
+
+        assert!(!has_changes);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("No Changes Detected"));
+    }
+
+    #[test]
+    fn test_diff_snapshots_rejects_different_binary_formats() {
+        let before = snapshot(&[("users", users())]);
+        let binary_formats = BinaryFormats::new(BinaryFormat::Hex, &[]).unwrap();
+        let mut writer =
+            SnapshotWriter::new(Vec::new(), &header().with_binary_formats(&binary_formats))
+                .unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let after = writer.finish().unwrap();
+
+        let error = diff_snapshots(
+            &mut SnapshotReader::new(before.as_slice()).unwrap(),
+            &mut SnapshotReader::new(after.as_slice()).unwrap(),
+            &mut DiffReport::new(HtmlOutputAdapter::new(Vec::new())),
+        )
+        .err()
+        .unwrap();
+
+        assert!(error.to_string().contains("different formats"));
+    }
+
+    #[test]
+    fn test_diff_snapshots_rejects_different_timezones() {
+        let before = snapshot(&[("users", users())]);
+        let timezone = "+09:00".parse().unwrap();
+        let mut writer =
+            SnapshotWriter::new(Vec::new(), &header().with_timezone(timezone)).unwrap();
+        writer.write_table("users", &users()).unwrap();
+        let after = writer.finish().unwrap();
+
+        let error = diff_snapshots(
+            &mut SnapshotReader::new(before.as_slice()).unwrap(),
+            &mut SnapshotReader::new(after.as_slice()).unwrap(),
+            &mut DiffReport::new(HtmlOutputAdapter::new(Vec::new())),
+        )
+        .err()
+        .unwrap();
+
+        assert!(error.to_string().contains("different time zones"));
+    }
+
+    #[tokio::test]
+    async fn test_diff_snapshots_reports_schema_changes() {
+        let db = TestDatabase::create("CREATE TABLE users (id INTEGER PRIMARY KEY);").await;
+        let adapter = db.adapter().await;
+        let mut snapshots = Vec::new();
+        for migration in [
+            "",
+            "ALTER TABLE users ADD COLUMN name TEXT; CREATE TABLE teams (id INTEGER);",
+        ] {
+            db.execute(migration).await;
+            let mut writer = SnapshotWriter::new(Vec::new(), &header()).unwrap();
+            writer
+                .write_database(&adapter, &TableFilter::default())
+                .await
+                .unwrap();
+            snapshots.push(writer.finish().unwrap());
+        }
+        let mut output = Vec::new();
+
+        let has_changes = diff_snapshots(
+            &mut SnapshotReader::new(snapshots[0].as_slice()).unwrap(),
+            &mut SnapshotReader::new(snapshots[1].as_slice()).unwrap(),
+            &mut DiffReport::new(JsonOutputAdapter::new(&mut output)),
+        )
+        .unwrap();
+
+        assert!(has_changes);
+        let report: Value = serde_json::from_slice(&output).unwrap();
+        let summaries: Vec<_> = report["schema_changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| {
+                serde_json::from_value::<SchemaChange>(change.clone())
+                    .unwrap()
+                    .summary()
+            })
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                "teams: table created (id INTEGER)",
+                "users.name: column added (TEXT)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Disk-backed row storage, so tables larger than memory can be compared.
+//!
+//! Rows are kept in memory until a table grows past a threshold. After that every row
+//! is appended to one of `PARTITION_COUNT` temporary files, chosen by a hash of the
+//! row's identity. Two tables partitioned this way can be compared one partition at a
+//! time, since a row can only ever match rows in the same partition.
+//!
+//! What is found in each partition can be kept as a sorted run in `SortedRuns`, which
+//! spills the same way and merges the runs back into one sorted sequence.
+
+use crate::error::Result;
+use crate::models::Row;
+use md5::{Digest, Md5};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// Number of rows kept in memory before a table is spilled to disk
+pub const SPILL_THRESHOLD_ROWS: usize = 100_000;
+
+/// Number of files a spilled table is partitioned into
+pub const PARTITION_COUNT: usize = 256;
+
+/// Bytes buffered per partition before they are appended to its file
+const PARTITION_BUFFER_BYTES: usize = 64 * 1024;
+
+/// A row together with its identity (row key or content hash)
+pub type IdentifiedRow = (String, Row);
+
+/// Rows of one table, held in memory or spilled to partitioned temporary files
+pub struct SpillTable {
+    key_columns: Vec<String>,
+    threshold: usize,
+    rows: Vec<IdentifiedRow>,
+    spill: Option<SpillFiles>,
+}
+
+/// Temporary partition files of a spilled table, removed when dropped.
+///
+/// Files are only opened while a buffer is appended, so any number of tables can be
+/// spilled without running out of file descriptors.
+struct SpillFiles {
+    dir: SpillDir,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Temporary directory of spill files, readable only by the current user (rows may
+/// hold personal data) and removed when dropped
+struct SpillDir {
+    dir: TempDir,
+}
+
+/// Sorted runs of items, held in memory or spilled to temporary files, that are
+/// merged back into one sorted sequence
+pub struct SortedRuns<T> {
+    threshold: usize,
+    runs: Vec<Vec<T>>,
+    len: usize,
+    spill: Option<SpillDir>,
+    spilled_runs: usize,
+}
+
+/// Items of sorted runs merged in order, reading one item ahead in each run
+pub struct MergedRuns<T, F> {
+    sources: Vec<Box<dyn Iterator<Item = Result<T>>>>,
+    /// Next item of each unfinished run with its source, largest first
+    heads: Vec<(T, usize)>,
+    compare: F,
+    _spill: Option<SpillDir>,
+}
+
+impl SpillTable {
+    pub fn new(key_columns: Vec<String>) -> Self {
+        Self {
+            key_columns,
+            threshold: SPILL_THRESHOLD_ROWS,
+            rows: Vec::new(),
+            spill: None,
+        }
+    }
+
+    /// Set the number of rows kept in memory before spilling to disk
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Number of rows kept in memory before spilling to disk
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Key columns of the table
+    pub fn key_columns(&self) -> &[String] {
+        &self.key_columns
+    }
+
+    /// Whether the rows have been spilled to disk
+    pub fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Add a row identified by `identity`
+    pub fn push(&mut self, identity: String, row: Row) -> Result<()> {
+        if let Some(spill) = &mut self.spill {
+            return spill.write(&(identity, row));
+        }
+
+        self.rows.push((identity, row));
+        if self.rows.len() > self.threshold {
+            let mut spill = SpillFiles::create()?;
+            for row in self.rows.drain(..) {
+                spill.write(&row)?;
+            }
+            self.spill = Some(spill);
+        }
+        Ok(())
+    }
+
+    /// Flush spilled rows so that the partitions can be read
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(spill) = &mut self.spill {
+            for index in 0..PARTITION_COUNT {
+                spill.flush(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of partitions the rows are stored in
+    pub fn partition_count(&self) -> usize {
+        if self.is_spilled() {
+            PARTITION_COUNT
+        } else {
+            1
+        }
+    }
+
+    /// Rows of partition `index` when the table is split into `count` partitions.
+    ///
+    /// `count` must be 1 or `PARTITION_COUNT`; a table held in memory can be split
+    /// either way, so it can be compared with a spilled one.
+    pub fn partition(&self, index: usize, count: usize) -> Result<Cow<'_, [IdentifiedRow]>> {
+        match &self.spill {
+            Some(spill) => spill.read(index).map(Cow::Owned),
+            None if count == 1 => Ok(Cow::Borrowed(&self.rows)),
+            None => Ok(Cow::Owned(
+                self.rows
+                    .iter()
+                    .filter(|(identity, _)| partition_of(identity) == index)
+                    .cloned()
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> SortedRuns<T> {
+    pub fn new() -> Self {
+        Self {
+            threshold: SPILL_THRESHOLD_ROWS,
+            runs: Vec::new(),
+            len: 0,
+            spill: None,
+            spilled_runs: 0,
+        }
+    }
+
+    /// Set the number of items kept in memory before spilling to disk
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Whether the runs have been spilled to disk
+    pub fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Add a run of items, already sorted
+    pub fn push(&mut self, run: Vec<T>) -> Result<()> {
+        if run.is_empty() {
+            return Ok(());
+        }
+        if self.spill.is_some() {
+            return self.write(&run);
+        }
+
+        self.len += run.len();
+        self.runs.push(run);
+        if self.len > self.threshold {
+            self.spill = Some(SpillDir::create()?);
+            for run in std::mem::take(&mut self.runs) {
+                self.write(&run)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the runs into one sequence ordered by `compare`, the order each run is
+    /// sorted in.
+    ///
+    /// A spilled run's file stays open until the run has been read.
+    pub fn merge<F>(self, compare: F) -> Result<MergedRuns<T, F>>
+    where
+        F: Fn(&T, &T) -> Ordering,
+    {
+        let mut sources: Vec<Box<dyn Iterator<Item = Result<T>>>> = Vec::new();
+        for run in self.runs {
+            sources.push(Box::new(run.into_iter().map(Ok)));
+        }
+        if let Some(spill) = &self.spill {
+            for index in 0..self.spilled_runs {
+                let file = File::open(spill.join(index))?;
+                sources.push(Box::new(
+                    BufReader::new(file)
+                        .lines()
+                        .map(|line| Ok(serde_json::from_str(&line?)?)),
+                ));
+            }
+        }
+
+        let mut merged = MergedRuns {
+            sources,
+            heads: Vec::new(),
+            compare,
+            _spill: self.spill,
+        };
+        for source in 0..merged.sources.len() {
+            merged.advance(source)?;
+        }
+        Ok(merged)
+    }
+
+    /// Append a run to a new file, one JSON line per item
+    fn write(&mut self, run: &[T]) -> Result<()> {
+        let Some(spill) = &self.spill else {
+            return Ok(());
+        };
+        let mut writer = BufWriter::new(File::create(spill.join(self.spilled_runs))?);
+        for item in run {
+            serde_json::to_writer(&mut writer, item)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        self.spilled_runs += 1;
+        Ok(())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> Default for SortedRuns<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> MergedRuns<T, F> {
+    /// Read the next item of a run into the heads
+    fn advance(&mut self, source: usize) -> Result<()> {
+        if let Some(item) = self.sources[source].next().transpose()? {
+            let index = self
+                .heads
+                .partition_point(|(head, _)| (self.compare)(head, &item).is_gt());
+            self.heads.insert(index, (item, source));
+        }
+        Ok(())
+    }
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> Iterator for MergedRuns<T, F> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (item, source) = self.heads.pop()?;
+        Some(self.advance(source).map(|_| item))
+    }
+}
+
+impl SpillDir {
+    fn create() -> Result<Self> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("dbba_");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(std::fs::Permissions::from_mode(0o700));
+        }
+        Ok(Self {
+            dir: builder.tempdir()?,
+        })
+    }
+
+    /// Path of the file numbered `index`
+    fn join(&self, index: usize) -> PathBuf {
+        self.dir.path().join(index.to_string())
+    }
+}
+
+impl SpillFiles {
+    fn create() -> Result<Self> {
+        Ok(Self {
+            dir: SpillDir::create()?,
+            buffers: vec![Vec::new(); PARTITION_COUNT],
+        })
+    }
+
+    /// Append a row to its partition as one JSON line
+    fn write(&mut self, row: &IdentifiedRow) -> Result<()> {
+        let index = partition_of(&row.0);
+        let buffer = &mut self.buffers[index];
+        serde_json::to_writer(&mut *buffer, row)?;
+        buffer.push(b'\n');
+        if buffer.len() >= PARTITION_BUFFER_BYTES {
+            self.flush(index)?;
+        }
+        Ok(())
+    }
+
+    /// Append the buffered rows of a partition to its file
+    fn flush(&mut self, index: usize) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(index))?;
+        file.write_all(&self.buffers[index])?;
+        self.buffers[index].clear();
+        Ok(())
+    }
+
+    fn read(&self, index: usize) -> Result<Vec<IdentifiedRow>> {
+        let file = File::open(self.path(index))?;
+        BufReader::new(file)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        self.dir.join(index)
+    }
+}
+
+/// Partition a row belongs to, by the hash of its identity
+fn partition_of(identity: &str) -> usize {
+    Md5::digest(identity.as_bytes())[0] as usize % PARTITION_COUNT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(id: i64) -> IdentifiedRow {
+        let row: Row = [("id".to_string(), json!(id))].into_iter().collect();
+        (id.to_string(), row)
+    }
+
+    fn all_rows(table: &SpillTable, count: usize) -> Vec<IdentifiedRow> {
+        let mut rows: Vec<_> = (0..count)
+            .flat_map(|index| table.partition(index, count).unwrap().into_owned())
+            .collect();
+        rows.sort_by_key(|(identity, _)| identity.parse::<i64>().unwrap());
+        rows
+    }
+
+    #[test]
+    fn test_small_table_stays_in_memory() {
+        let mut table = SpillTable::new(vec!["id".to_string()]);
+        table.push(row(1).0, row(1).1).unwrap();
+        table.finish().unwrap();
+
+        assert!(!table.is_spilled());
+        assert_eq!(table.partition_count(), 1);
+        assert_eq!(all_rows(&table, 1), vec![row(1)]);
+    }
+
+    #[test]
+    fn test_spills_past_threshold() {
+        let mut table = SpillTable::new(vec!["id".to_string()]).with_threshold(2);
+        for id in 0..10 {
+            let (identity, r) = row(id);
+            table.push(identity, r).unwrap();
+        }
+        table.finish().unwrap();
+
+        assert!(table.is_spilled());
+        assert_eq!(table.partition_count(), PARTITION_COUNT);
+        assert_eq!(
+            all_rows(&table, PARTITION_COUNT),
+            (0..10).map(row).collect::<Vec<_>>()
+        );
+
+        let dir = table.spill.as_ref().unwrap().dir.dir.path().to_path_buf();
+        drop(table);
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spill_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let spill = SpillDir::create().unwrap();
+        let mode = std::fs::metadata(spill.dir.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[test]
+    fn test_in_memory_partitions_match_spilled_partitions() {
+        let mut memory = SpillTable::new(Vec::new());
+        let mut spilled = SpillTable::new(Vec::new()).with_threshold(0);
+        for id in 0..50 {
+            let (identity, r) = row(id);
+            memory.push(identity.clone(), r.clone()).unwrap();
+            spilled.push(identity, r).unwrap();
+        }
+        spilled.finish().unwrap();
+
+        for index in 0..PARTITION_COUNT {
+            assert_eq!(
+                memory.partition(index, PARTITION_COUNT).unwrap(),
+                spilled.partition(index, PARTITION_COUNT).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sorted_runs_merge_in_order() {
+        let runs = [vec![1, 4, 9], vec![], vec![2, 3, 10], vec![5]];
+        for threshold in [usize::MAX, 0] {
+            let mut sorted = SortedRuns::new().with_threshold(threshold);
+            for run in runs.clone() {
+                sorted.push(run).unwrap();
+            }
+            assert_eq!(sorted.is_spilled(), threshold == 0);
+
+            let merged: Vec<i64> = sorted
+                .merge(|a: &i64, b: &i64| a.cmp(b))
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(merged, vec![1, 2, 3, 4, 5, 9, 10]);
+        }
+    }
+}